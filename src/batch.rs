@@ -0,0 +1,530 @@
+use crate::{
+    client::{Client, ClientError},
+    sendable::Sendable,
+    transport::HttpResponse,
+};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE},
+    Method, StatusCode,
+};
+use url::Url;
+
+/// The maximum number of calls Google accepts in a single Calendar batch request. Larger batches
+/// are split into several requests.
+pub const MAX_BATCH_SIZE: usize = 50;
+
+#[derive(Debug, Clone)]
+struct BatchOperation {
+    method: Method,
+    url: Url,
+    body: Option<Vec<u8>>,
+}
+
+/// Batch collects many operations into `multipart/mixed` requests to the Calendar batch endpoint.
+/// Construct one with Client::batch. Each operation receives its own result, so a failure of one
+/// operation does not fail the others.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    client: Client,
+    ops: Vec<BatchOperation>,
+}
+
+/// BatchResponse is the response to a single operation within a batch.
+#[derive(Debug, Clone)]
+pub struct BatchResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl BatchResponse {
+    /// Deserialize the response body.
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T, ClientError> {
+        crate::transport::decode(&self.body)
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).to_string()
+    }
+}
+
+impl Batch {
+    pub(crate) fn new(client: Client) -> Self {
+        Self {
+            client,
+            ops: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    fn push(
+        &mut self,
+        method: Method,
+        url: Url,
+        body: Option<Vec<u8>>,
+    ) -> Result<usize, ClientError> {
+        self.ops.push(BatchOperation { method, url, body });
+        Ok(self.ops.len() - 1)
+    }
+
+    /// Add a GET request. Returns the index of its result in the output of send.
+    pub fn get(
+        &mut self,
+        action: Option<String>,
+        target: impl Sendable,
+    ) -> Result<usize, ClientError> {
+        let url = self.client.get_url(&target, action)?;
+        self.push(Method::GET, url, None)
+    }
+
+    /// Add a POST request. Returns the index of its result in the output of send.
+    pub fn post(
+        &mut self,
+        action: Option<String>,
+        target: impl Sendable,
+    ) -> Result<usize, ClientError> {
+        let url = self.client.get_url(&target, action)?;
+        self.push(Method::POST, url, Some(target.body_bytes()?))
+    }
+
+    /// Add a PUT request. Returns the index of its result in the output of send.
+    pub fn put(
+        &mut self,
+        action: Option<String>,
+        target: impl Sendable,
+    ) -> Result<usize, ClientError> {
+        let url = self.client.get_url(&target, action)?;
+        self.push(Method::PUT, url, Some(target.body_bytes()?))
+    }
+
+    /// Add a PATCH request. Returns the index of its result in the output of send.
+    pub fn patch(
+        &mut self,
+        action: Option<String>,
+        target: impl Sendable,
+    ) -> Result<usize, ClientError> {
+        let url = self.client.get_url(&target, action)?;
+        self.push(Method::PATCH, url, Some(target.body_bytes()?))
+    }
+
+    /// Add a DELETE request. Returns the index of its result in the output of send.
+    pub fn delete(
+        &mut self,
+        action: Option<String>,
+        target: impl Sendable,
+    ) -> Result<usize, ClientError> {
+        let url = self.client.get_url(&target, action)?;
+        self.push(Method::DELETE, url, None)
+    }
+
+    /// Send the batch. The outer error is only returned if a batch request as a whole failed;
+    /// otherwise the results are returned in the order the operations were added.
+    pub async fn send(self) -> Result<Vec<Result<BatchResponse, ClientError>>, ClientError> {
+        let url = batch_url(self.client.base_url())?;
+        let mut results = Vec::with_capacity(self.ops.len());
+
+        for chunk in self.ops.chunks(MAX_BATCH_SIZE) {
+            let boundary = format!("batch_{}", uuid::Uuid::new_v4().simple());
+
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                CONTENT_TYPE,
+                HeaderValue::from_str(&format!("multipart/mixed; boundary={}", boundary))
+                    .map_err(|e| ClientError::UnknownError(e.to_string()))?,
+            );
+
+            let resp = self
+                .client
+                .execute(
+                    Method::POST,
+                    url.clone(),
+                    headers,
+                    Some(encode(chunk, &boundary)),
+                )
+                .await?;
+
+            let boundary = resp
+                .headers()
+                .get(CONTENT_TYPE)
+                .map(|h| h.to_str())
+                .transpose()?
+                .and_then(response_boundary)
+                .ok_or_else(|| {
+                    ClientError::UnknownError(
+                        "batch response is missing a multipart boundary".to_string(),
+                    )
+                })?;
+
+            let mut parsed = decode(&resp.text(), &boundary, chunk.len());
+            results.append(&mut parsed);
+        }
+
+        Ok(results)
+    }
+}
+
+/// The batch endpoint lives at /batch/calendar/v3 relative to the API root /calendar/v3.
+fn batch_url(base_url: &str) -> Result<Url, ClientError> {
+    let mut url = Url::parse(base_url)?;
+    let path = format!("/batch{}", url.path().trim_end_matches('/'));
+    url.set_path(&path);
+    Ok(url)
+}
+
+fn encode(ops: &[BatchOperation], boundary: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+
+    for (i, op) in ops.iter().enumerate() {
+        let mut target = op.url.path().to_string();
+        if let Some(query) = op.url.query() {
+            target.push('?');
+            target.push_str(query);
+        }
+
+        out.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Type: application/http\r\nContent-ID: <item{}>\r\n\r\n{} {} HTTP/1.1\r\n",
+                boundary, i, op.method, target
+            )
+            .as_bytes(),
+        );
+
+        match &op.body {
+            Some(body) => {
+                out.extend_from_slice(
+                    format!(
+                        "Content-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+                        body.len()
+                    )
+                    .as_bytes(),
+                );
+                out.extend_from_slice(body);
+                out.extend_from_slice(b"\r\n");
+            }
+            None => out.extend_from_slice(b"\r\n"),
+        }
+    }
+
+    out.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    out
+}
+
+/// A request read back out of a batch body by decode_requests.
+#[derive(Debug, Clone)]
+pub(crate) struct BatchPart {
+    pub(crate) method: Method,
+    pub(crate) target: String,
+    pub(crate) body: Option<Vec<u8>>,
+}
+
+/// The inverse of encode, used to answer batches without sending them.
+pub(crate) fn decode_requests(body: &str, boundary: &str) -> Vec<BatchPart> {
+    let delimiter = format!("--{}", boundary);
+
+    body.split(&delimiter)
+        .skip(1)
+        .take_while(|part| !part.starts_with("--"))
+        .filter_map(|part| {
+            let (_, inner) = split_headers(part.trim_start_matches(['\r', '\n']));
+            let (lines, body) = split_headers(inner);
+            let mut request_line = lines.first()?.split_whitespace();
+            let method = request_line.next()?.parse::<Method>().ok()?;
+            let target = request_line.next()?.to_string();
+            let body = body.trim_end_matches(['\r', '\n']);
+
+            Some(BatchPart {
+                method,
+                target,
+                body: (!body.is_empty()).then(|| body.as_bytes().to_vec()),
+            })
+        })
+        .collect()
+}
+
+/// Encode the responses as a batch response body, in the order of the requests they answer.
+pub(crate) fn encode_responses(responses: &[HttpResponse], boundary: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+
+    for (i, resp) in responses.iter().enumerate() {
+        out.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Type: application/http\r\nContent-ID: <response-item{}>\r\n\r\nHTTP/1.1 {}\r\n",
+                boundary, i, resp.status()
+            )
+            .as_bytes(),
+        );
+
+        for (name, value) in resp.headers() {
+            out.extend_from_slice(name.as_str().as_bytes());
+            out.extend_from_slice(b": ");
+            out.extend_from_slice(value.as_bytes());
+            out.extend_from_slice(b"\r\n");
+        }
+
+        out.extend_from_slice(b"\r\n");
+        out.extend_from_slice(resp.bytes());
+        out.extend_from_slice(b"\r\n");
+    }
+
+    out.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    out
+}
+
+pub(crate) fn response_boundary(content_type: &str) -> Option<String> {
+    content_type.split(';').find_map(|param| {
+        param
+            .trim()
+            .strip_prefix("boundary=")
+            .map(|b| b.trim_matches('"').to_string())
+    })
+}
+
+/// Split a line-oriented block into its header lines and the remainder after the first blank
+/// line.
+fn split_headers(block: &str) -> (Vec<&str>, &str) {
+    let mut lines = Vec::new();
+    let mut rest = block;
+
+    while let Some(pos) = rest.find('\n') {
+        let line = rest[..pos].trim_end_matches('\r');
+        rest = &rest[pos + 1..];
+
+        if line.is_empty() {
+            return (lines, rest);
+        }
+
+        lines.push(line);
+    }
+
+    lines.push(rest.trim_end_matches('\r'));
+    (lines, "")
+}
+
+fn decode(body: &str, boundary: &str, count: usize) -> Vec<Result<BatchResponse, ClientError>> {
+    let mut results: Vec<Option<Result<BatchResponse, ClientError>>> = vec![None; count];
+    let delimiter = format!("--{}", boundary);
+
+    for part in body.split(&delimiter).skip(1) {
+        if part.starts_with("--") {
+            break;
+        }
+
+        let part = part.trim_start_matches(['\r', '\n']);
+        let (outer, inner) = split_headers(part);
+
+        let index = outer.iter().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            if !name.trim().eq_ignore_ascii_case("content-id") {
+                return None;
+            }
+
+            value
+                .trim()
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .strip_prefix("response-item")?
+                .parse::<usize>()
+                .ok()
+        });
+
+        let index = match index {
+            Some(index) if index < count => index,
+            _ => continue,
+        };
+
+        results[index] = Some(decode_part(inner));
+    }
+
+    results
+        .into_iter()
+        .enumerate()
+        .map(|(i, res)| {
+            res.unwrap_or_else(|| {
+                Err(ClientError::UnknownError(format!(
+                    "no response for batch item {}",
+                    i
+                )))
+            })
+        })
+        .collect()
+}
+
+fn decode_part(inner: &str) -> Result<BatchResponse, ClientError> {
+    let (lines, body) = split_headers(inner);
+    let mut lines = lines.into_iter();
+
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .ok_or_else(|| ClientError::UnknownError("malformed batch response part".to_string()))?;
+
+    let mut headers = HeaderMap::new();
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.trim().as_bytes()),
+                HeaderValue::from_str(value.trim()),
+            ) {
+                headers.append(name, value);
+            }
+        }
+    }
+
+    let body = body.trim_end_matches(['\r', '\n']).to_string();
+
+    if status.is_client_error() || status.is_server_error() {
+        return Err(ClientError::from_response(status, &headers, body));
+    }
+
+    Ok(BatchResponse {
+        status,
+        headers,
+        body: body.into_bytes(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(method: Method, url: &str, body: Option<&str>) -> BatchOperation {
+        BatchOperation {
+            method,
+            url: Url::parse(url).unwrap(),
+            body: body.map(|b| b.as_bytes().to_vec()),
+        }
+    }
+
+    fn response(status: StatusCode, body: &str) -> HttpResponse {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        HttpResponse {
+            status,
+            headers,
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn requests_round_trip() {
+        let ops = vec![
+            op(
+                Method::GET,
+                "https://example.com/calendar/v3/calendars/primary/events/e1?fields=id",
+                None,
+            ),
+            op(
+                Method::POST,
+                "https://example.com/calendar/v3/calendars/primary/events",
+                Some(r#"{"summary":"lunch"}"#),
+            ),
+            op(
+                Method::DELETE,
+                "https://example.com/calendar/v3/calendars/primary/events/e2",
+                None,
+            ),
+        ];
+
+        let body = encode(&ops, "batch_test");
+        let parts = decode_requests(&String::from_utf8(body).unwrap(), "batch_test");
+
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].method, Method::GET);
+        assert_eq!(
+            parts[0].target,
+            "/calendar/v3/calendars/primary/events/e1?fields=id"
+        );
+        assert_eq!(parts[0].body, None);
+        assert_eq!(parts[1].method, Method::POST);
+        assert_eq!(parts[1].target, "/calendar/v3/calendars/primary/events");
+        assert_eq!(
+            parts[1].body.as_deref(),
+            Some(br#"{"summary":"lunch"}"#.as_slice())
+        );
+        assert_eq!(parts[2].method, Method::DELETE);
+        assert_eq!(parts[2].body, None);
+    }
+
+    #[test]
+    fn responses_round_trip() {
+        let responses = vec![
+            response(StatusCode::OK, r#"{"id":"e1"}"#),
+            response(
+                StatusCode::NOT_FOUND,
+                r#"{"error":{"code":404,"message":"Not Found","errors":[]}}"#,
+            ),
+            response(StatusCode::NO_CONTENT, ""),
+        ];
+
+        let body = encode_responses(&responses, "batch_test");
+        let results = decode(&String::from_utf8(body).unwrap(), "batch_test", 4);
+
+        assert_eq!(results.len(), 4);
+
+        let first = results[0].as_ref().unwrap();
+        assert_eq!(first.status, StatusCode::OK);
+        assert_eq!(first.text(), r#"{"id":"e1"}"#);
+        assert_eq!(first.headers.get(CONTENT_TYPE).unwrap(), "application/json");
+
+        assert!(matches!(results[1], Err(ClientError::NotFound(_))));
+        assert_eq!(results[2].as_ref().unwrap().status, StatusCode::NO_CONTENT);
+        // a missing response is reported for its own item only.
+        assert!(matches!(results[3], Err(ClientError::UnknownError(_))));
+    }
+
+    #[test]
+    fn json_reports_decode_errors() {
+        let results = decode(
+            &String::from_utf8(encode_responses(
+                &[response(StatusCode::OK, r#"{"id":1}"#)],
+                "batch_test",
+            ))
+            .unwrap(),
+            "batch_test",
+            1,
+        );
+
+        let err = results[0]
+            .as_ref()
+            .unwrap()
+            .json::<crate::resources::Event>()
+            .unwrap_err();
+        match err {
+            ClientError::Decode(e) => assert_eq!(e.path, "id"),
+            e => panic!("expected a decode error, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn boundary_from_content_type() {
+        assert_eq!(
+            response_boundary("multipart/mixed; boundary=batch_abc").as_deref(),
+            Some("batch_abc")
+        );
+        assert_eq!(
+            response_boundary(r#"multipart/mixed; boundary="batch_abc""#).as_deref(),
+            Some("batch_abc")
+        );
+        assert_eq!(response_boundary("application/json"), None);
+    }
+
+    #[test]
+    fn batch_url_from_base_url() {
+        assert_eq!(
+            batch_url("https://www.googleapis.com/calendar/v3")
+                .unwrap()
+                .as_str(),
+            "https://www.googleapis.com/batch/calendar/v3"
+        );
+    }
+}
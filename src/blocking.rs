@@ -0,0 +1,583 @@
+//! Synchronous facades over the async clients, for scripts and tools which do not otherwise use
+//! an async runtime. Every facade blocks on one small internal tokio runtime, shared across the
+//! process, so none of these may be called from within an async context.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use gcal::blocking::*;
+//!
+//! fn main() -> Result<(), anyhow::Error> {
+//!     let access_key = std::env::args().nth(1).expect("Provide an access key");
+//!     let now = chrono::Local::now();
+//!     let client = EventClient::new(Client::new(access_key)?);
+//!     let list = client.list("primary".to_string(), now - chrono::Duration::days(1), now)?;
+//!
+//!     for event in &list {
+//!         eprintln!("{:?} {:?}", event.id, event.summary);
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+use crate::{
+    batch::{Batch, BatchResponse},
+    cache::ResponseCache,
+    cassette::Cassette,
+    client::{ApiResponse, ClientError, Conditional},
+    dryrun::DryRun,
+    middleware::Middleware,
+    oauth::{AccessToken, ClientParameters, State},
+    ratelimit::{ConcurrencyLimit, QuotaGovernor},
+    resources::{CalendarList, CalendarListItem, Event, Events, SendUpdates},
+    retry::RetryPolicy,
+    sendable::{Fields, Sendable},
+    token::TokenProvider,
+    transport::{HttpResponse, Transport},
+};
+use reqwest::{header::HeaderMap, Method};
+use std::{
+    future::Future,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+use tokio::runtime::Runtime;
+
+pub use crate::oauth::oauth_user_url;
+
+static RUNTIME: OnceLock<Arc<Runtime>> = OnceLock::new();
+
+/// The runtime shared by every facade, built on first use.
+fn runtime() -> Result<Arc<Runtime>, ClientError> {
+    if let Some(rt) = RUNTIME.get() {
+        return Ok(rt.clone());
+    }
+
+    // A multi-threaded runtime keeps spawned tasks, such as the oauth_listener server, running
+    // between calls.
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("gcal-blocking")
+        .enable_all()
+        .build()
+        .map_err(|e| ClientError::UnknownError(e.to_string()))?;
+
+    // Another thread may have won the race; its runtime is used and this one dropped.
+    Ok(RUNTIME.get_or_init(|| Arc::new(rt)).clone())
+}
+
+/// Client is the synchronous counterpart of crate::Client.
+#[derive(Debug, Clone)]
+pub struct Client {
+    inner: crate::Client,
+    rt: Arc<Runtime>,
+}
+
+impl Client {
+    /// Create a new client. Requires an access key.
+    pub fn new(access_key: String) -> Result<Self, ClientError> {
+        Self::from_async(crate::Client::new(access_key)?)
+    }
+
+    /// Create a new client which obtains its access tokens from the provider.
+    pub fn with_token_provider(
+        provider: impl TokenProvider + 'static,
+    ) -> Result<Self, ClientError> {
+        Self::from_async(crate::Client::with_token_provider(provider)?)
+    }
+
+    /// Wrap an already configured async client.
+    pub fn from_async(client: crate::Client) -> Result<Self, ClientError> {
+        Ok(Self {
+            inner: client,
+            rt: runtime()?,
+        })
+    }
+
+    /// The async client requests are sent through.
+    pub fn as_async(&self) -> &crate::Client {
+        &self.inner
+    }
+
+    fn block_on<F: Future>(&self, f: F) -> F::Output {
+        self.rt.block_on(f)
+    }
+
+    /// See crate::Client::set_debug.
+    pub fn set_debug(&mut self) {
+        self.inner.set_debug()
+    }
+
+    /// See crate::Client::add_middleware.
+    pub fn add_middleware(&mut self, middleware: impl Middleware + 'static) {
+        self.inner.add_middleware(middleware)
+    }
+
+    /// See crate::Client::set_base_url.
+    pub fn set_base_url(&mut self, base_url: &str) -> Result<(), ClientError> {
+        self.inner.set_base_url(base_url)
+    }
+
+    /// The API root requests are currently sent to.
+    pub fn base_url(&self) -> &str {
+        self.inner.base_url()
+    }
+
+    /// See crate::Client::allow_plain_http.
+    pub fn allow_plain_http(&mut self) -> Result<(), ClientError> {
+        self.inner.allow_plain_http()
+    }
+
+    /// See crate::Client::set_default_headers.
+    pub fn set_default_headers(&mut self, headers: HeaderMap) {
+        self.inner.set_default_headers(headers)
+    }
+
+    /// See crate::Client::set_quota_user.
+    pub fn set_quota_user(&mut self, quota_user: Option<String>) {
+        self.inner.set_quota_user(quota_user)
+    }
+
+    /// See crate::Client::set_transport.
+    pub fn set_transport(&mut self, transport: impl Transport + 'static) {
+        self.inner.set_transport(transport)
+    }
+
+    /// See crate::Client::set_retry_policy.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.inner.set_retry_policy(policy)
+    }
+
+    /// See crate::Client::set_quota_governor.
+    pub fn set_quota_governor(&mut self, governor: QuotaGovernor) {
+        self.inner.set_quota_governor(governor)
+    }
+
+    /// See crate::Client::set_concurrency_limit.
+    pub fn set_concurrency_limit(&mut self, limit: ConcurrencyLimit) {
+        self.inner.set_concurrency_limit(limit)
+    }
+
+    /// See crate::Client::set_cassette.
+    pub fn set_cassette(&mut self, cassette: Cassette) {
+        self.inner.set_cassette(cassette)
+    }
+
+    /// See crate::Client::set_dry_run.
+    pub fn set_dry_run(&mut self, dry_run: DryRun) {
+        self.inner.set_dry_run(dry_run)
+    }
+
+    /// See crate::Client::set_response_cache.
+    pub fn set_response_cache(&mut self, cache: ResponseCache) {
+        self.inner.set_response_cache(cache)
+    }
+
+    /// Start a batch of requests; send it with send_batch.
+    pub fn batch(&self) -> Batch {
+        self.inner.batch()
+    }
+
+    /// Send the batch, returning the result of each operation in the order they were added.
+    pub fn send_batch(
+        &self,
+        batch: Batch,
+    ) -> Result<Vec<Result<BatchResponse, ClientError>>, ClientError> {
+        self.block_on(batch.send())
+    }
+
+    /// Perform a request with additional headers.
+    pub fn request(
+        &self,
+        method: Method,
+        action: Option<String>,
+        target: impl Sendable,
+        headers: HeaderMap,
+    ) -> Result<HttpResponse, ClientError> {
+        self.block_on(self.inner.request(method, action, target, headers))
+    }
+
+    /// Send the target with its own method and action, returning the deserialized response.
+    pub fn send<S: Sendable>(&self, target: S) -> Result<S::Response, ClientError> {
+        self.block_on(self.inner.send(target))
+    }
+
+    /// Perform a GET request.
+    pub fn get(
+        &self,
+        action: Option<String>,
+        target: impl Sendable,
+    ) -> Result<HttpResponse, ClientError> {
+        self.block_on(self.inner.get(action, target))
+    }
+
+    /// Perform a POST request.
+    pub fn post(
+        &self,
+        action: Option<String>,
+        target: impl Sendable,
+    ) -> Result<HttpResponse, ClientError> {
+        self.block_on(self.inner.post(action, target))
+    }
+
+    /// Perform a PUT request.
+    pub fn put(
+        &self,
+        action: Option<String>,
+        target: impl Sendable,
+    ) -> Result<HttpResponse, ClientError> {
+        self.block_on(self.inner.put(action, target))
+    }
+
+    /// Perform a PATCH request.
+    pub fn patch(
+        &self,
+        action: Option<String>,
+        target: impl Sendable,
+    ) -> Result<HttpResponse, ClientError> {
+        self.block_on(self.inner.patch(action, target))
+    }
+
+    /// Perform a DELETE request.
+    pub fn delete(
+        &self,
+        action: Option<String>,
+        target: impl Sendable,
+    ) -> Result<HttpResponse, ClientError> {
+        self.block_on(self.inner.delete(action, target))
+    }
+}
+
+/// EventClient is the synchronous counterpart of crate::EventClient.
+#[derive(Debug, Clone)]
+pub struct EventClient(crate::EventClient, Client);
+
+impl EventClient {
+    /// Construct a new EventClient. Requires a blocking Client.
+    pub fn new(client: Client) -> Self {
+        Self(crate::EventClient::new(client.inner.clone()), client)
+    }
+
+    /// Only request the selected fields when getting and listing events.
+    pub fn with_fields(self, fields: Fields) -> Self {
+        Self(self.0.with_fields(fields), self.1)
+    }
+
+    /// Delete the event.
+    pub fn delete(&self, event: Event) -> Result<(), ClientError> {
+        self.1.block_on(self.0.delete(event))
+    }
+
+    /// As with delete, also returning the metadata of the response.
+    pub fn delete_with_meta(&self, event: Event) -> Result<ApiResponse<()>, ClientError> {
+        self.1.block_on(self.0.delete_with_meta(event))
+    }
+
+    /// Delete the event only if it has not changed since it was retrieved, according to its etag.
+    pub fn delete_if_match(&self, event: Event) -> Result<(), ClientError> {
+        self.1.block_on(self.0.delete_if_match(event))
+    }
+
+    /// As with delete_if_match, also returning the metadata of the response.
+    pub fn delete_if_match_with_meta(&self, event: Event) -> Result<ApiResponse<()>, ClientError> {
+        self.1.block_on(self.0.delete_if_match_with_meta(event))
+    }
+
+    /// Get an event by ID.
+    pub fn get(&self, calendar_id: String, event_id: String) -> Result<Event, ClientError> {
+        self.1.block_on(self.0.get(calendar_id, event_id))
+    }
+
+    /// As with get, also returning the metadata of the response.
+    pub fn get_with_meta(
+        &self,
+        calendar_id: String,
+        event_id: String,
+    ) -> Result<ApiResponse<Event>, ClientError> {
+        self.1.block_on(self.0.get_with_meta(calendar_id, event_id))
+    }
+
+    /// Get an event by ID, unless its etag still matches the one provided.
+    pub fn get_if_none_match(
+        &self,
+        calendar_id: String,
+        event_id: String,
+        etag: String,
+    ) -> Result<Conditional<Event>, ClientError> {
+        self.1
+            .block_on(self.0.get_if_none_match(calendar_id, event_id, etag))
+    }
+
+    /// As with get_if_none_match, also returning the metadata of the response.
+    pub fn get_if_none_match_with_meta(
+        &self,
+        calendar_id: String,
+        event_id: String,
+        etag: String,
+    ) -> Result<ApiResponse<Conditional<Event>>, ClientError> {
+        self.1.block_on(
+            self.0
+                .get_if_none_match_with_meta(calendar_id, event_id, etag),
+        )
+    }
+
+    /// Import an event.
+    pub fn import(&self, event: Event) -> Result<Event, ClientError> {
+        self.1.block_on(self.0.import(event))
+    }
+
+    /// As with import, also returning the metadata of the response.
+    pub fn import_with_meta(&self, event: Event) -> Result<ApiResponse<Event>, ClientError> {
+        self.1.block_on(self.0.import_with_meta(event))
+    }
+
+    /// Insert an event.
+    pub fn insert(&self, event: Event) -> Result<Event, ClientError> {
+        self.1.block_on(self.0.insert(event))
+    }
+
+    /// As with insert, also returning the metadata of the response.
+    pub fn insert_with_meta(&self, event: Event) -> Result<ApiResponse<Event>, ClientError> {
+        self.1.block_on(self.0.insert_with_meta(event))
+    }
+
+    /// Insert an event so that it is safe to retry, returning the existing event on conflict.
+    pub fn insert_idempotent(
+        &self,
+        event: Event,
+        id: Option<String>,
+    ) -> Result<Event, ClientError> {
+        self.1.block_on(self.0.insert_idempotent(event, id))
+    }
+
+    /// As with insert_idempotent, also returning the metadata of the response.
+    pub fn insert_idempotent_with_meta(
+        &self,
+        event: Event,
+        id: Option<String>,
+    ) -> Result<ApiResponse<Event>, ClientError> {
+        self.1
+            .block_on(self.0.insert_idempotent_with_meta(event, id))
+    }
+
+    /// Retrieve all instances for a recurring event.
+    pub fn instances(&self, event: Event) -> Result<Events, ClientError> {
+        self.1.block_on(self.0.instances(event))
+    }
+
+    /// As with instances, also returning the metadata of the response.
+    pub fn instances_with_meta(&self, event: Event) -> Result<ApiResponse<Events>, ClientError> {
+        self.1.block_on(self.0.instances_with_meta(event))
+    }
+
+    /// List events between the start and end times. All pages are retrieved.
+    pub fn list(
+        &self,
+        calendar_id: String,
+        start_time: chrono::DateTime<chrono::Local>,
+        end_time: chrono::DateTime<chrono::Local>,
+    ) -> Result<Vec<Event>, ClientError> {
+        self.1
+            .block_on(self.0.list(calendar_id, start_time, end_time))
+    }
+
+    /// List events as with list, returning each page along with the metadata of the response it
+    /// came from.
+    pub fn list_with_meta(
+        &self,
+        calendar_id: String,
+        start_time: chrono::DateTime<chrono::Local>,
+        end_time: chrono::DateTime<chrono::Local>,
+    ) -> Result<Vec<ApiResponse<Events>>, ClientError> {
+        self.1
+            .block_on(self.0.list_with_meta(calendar_id, start_time, end_time))
+    }
+
+    /// Move event to another destination calendar_id.
+    pub fn move_to_calendar(
+        &self,
+        event: Event,
+        destination: String,
+        send_updates: Option<SendUpdates>,
+    ) -> Result<(), ClientError> {
+        self.1
+            .block_on(self.0.move_to_calendar(event, destination, send_updates))
+    }
+
+    /// As with move_to_calendar, also returning the metadata of the response.
+    pub fn move_to_calendar_with_meta(
+        &self,
+        event: Event,
+        destination: String,
+        send_updates: Option<SendUpdates>,
+    ) -> Result<ApiResponse<()>, ClientError> {
+        self.1.block_on(
+            self.0
+                .move_to_calendar_with_meta(event, destination, send_updates),
+        )
+    }
+
+    /// Add an event with the summary.
+    pub fn add(&self, text: String) -> Result<Event, ClientError> {
+        self.1.block_on(self.0.add(text))
+    }
+
+    /// As with add, also returning the metadata of the response.
+    pub fn add_with_meta(&self, text: String) -> Result<ApiResponse<Event>, ClientError> {
+        self.1.block_on(self.0.add_with_meta(text))
+    }
+
+    /// Update an event.
+    pub fn update(&self, event: Event) -> Result<Event, ClientError> {
+        self.1.block_on(self.0.update(event))
+    }
+
+    /// As with update, also returning the metadata of the response.
+    pub fn update_with_meta(&self, event: Event) -> Result<ApiResponse<Event>, ClientError> {
+        self.1.block_on(self.0.update_with_meta(event))
+    }
+
+    /// Update an event only if it has not changed since it was retrieved, according to its etag.
+    pub fn update_if_match(&self, event: Event) -> Result<Event, ClientError> {
+        self.1.block_on(self.0.update_if_match(event))
+    }
+
+    /// As with update_if_match, also returning the metadata of the response.
+    pub fn update_if_match_with_meta(
+        &self,
+        event: Event,
+    ) -> Result<ApiResponse<Event>, ClientError> {
+        self.1.block_on(self.0.update_if_match_with_meta(event))
+    }
+}
+
+/// CalendarListClient is the synchronous counterpart of crate::CalendarListClient.
+#[derive(Debug, Clone)]
+pub struct CalendarListClient(crate::CalendarListClient, Client);
+
+impl CalendarListClient {
+    /// Construct a CalendarListClient. Requires a blocking Client.
+    pub fn new(client: Client) -> Self {
+        Self(crate::CalendarListClient::new(client.inner.clone()), client)
+    }
+
+    /// Only request the selected fields when listing calendars.
+    pub fn with_fields(self, fields: Fields) -> Self {
+        Self(self.0.with_fields(fields), self.1)
+    }
+
+    /// List the calendars. All pages are retrieved.
+    pub fn list(&self) -> Result<Vec<CalendarListItem>, ClientError> {
+        self.1.block_on(self.0.list())
+    }
+
+    /// List the calendars as with list, returning each page along with the metadata of the
+    /// response it came from.
+    pub fn list_with_meta(&self) -> Result<Vec<ApiResponse<CalendarList>>, ClientError> {
+        self.1.block_on(self.0.list_with_meta())
+    }
+}
+
+/// Requests an access token. See crate::request_access_token.
+pub fn request_access_token(
+    client_params: ClientParameters,
+    code: Option<&str>,
+    state: Option<&str>,
+    refresh: bool,
+) -> Result<AccessToken, ClientError> {
+    Ok(runtime()?.block_on(crate::oauth::request_access_token(
+        client_params,
+        code,
+        state,
+        refresh,
+    ))?)
+}
+
+/// OAuthListener is a running oauth_listener. The listener stops when this is dropped.
+#[derive(Debug)]
+pub struct OAuthListener {
+    addr: String,
+    state: State,
+    server: tokio::task::JoinHandle<()>,
+    rt: Arc<Runtime>,
+}
+
+impl Drop for OAuthListener {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+impl OAuthListener {
+    /// The address of the listener, suitable for coercing to the redirect_url.
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    /// The client parameters as captured so far.
+    pub fn params(&self) -> ClientParameters {
+        self.rt.block_on(self.state.lock()).clone()
+    }
+
+    /// Block until an access token has been captured, or the timeout passes. Returns the
+    /// captured parameters, or None on timeout. Refused redirects do not end the wait, since a
+    /// forged one may arrive before the real one; see ClientParameters::oauth_error.
+    pub fn wait(&self, timeout: Duration) -> Option<ClientParameters> {
+        let started = std::time::Instant::now();
+
+        loop {
+            let params = self.params();
+            if params.access_key.is_some() {
+                return Some(params);
+            }
+
+            if started.elapsed() >= timeout {
+                return None;
+            }
+
+            std::thread::sleep(Duration::from_millis(250));
+        }
+    }
+}
+
+/// Create a local listener which is ready to become the redirect_url. See crate::oauth_listener.
+pub fn oauth_listener(state: State) -> Result<OAuthListener, ClientError> {
+    let rt = runtime()?;
+    let (addr, server) = rt
+        .block_on(crate::oauth::serve_listener(state.clone()))
+        .map_err(|e| ClientError::UnknownError(e.to_string()))?;
+
+    Ok(OAuthListener {
+        addr,
+        state,
+        server,
+        rt,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpStream;
+
+    #[test]
+    fn oauth_listener_stops_when_dropped() {
+        let listener = oauth_listener(State::default()).unwrap();
+        let addr = listener.addr().to_string();
+
+        // the server starts in the background.
+        let connects = |expected: bool| {
+            (0..40).any(|_| {
+                if TcpStream::connect(&addr).is_ok() == expected {
+                    return true;
+                }
+                std::thread::sleep(Duration::from_millis(50));
+                false
+            })
+        };
+
+        assert!(connects(true));
+        drop(listener);
+        assert!(connects(false));
+    }
+}
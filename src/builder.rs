@@ -0,0 +1,283 @@
+use crate::{
+    cache::ResponseCache,
+    client::{Client, ClientError},
+    dryrun::DryRun,
+    middleware::Middleware,
+    ratelimit::{ConcurrencyLimit, QuotaGovernor},
+    retry::RetryPolicy,
+    token::{StaticToken, TokenProvider},
+    transport::{ReqwestTransport, Transport},
+};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::{sync::Arc, time::Duration};
+
+/// ClientBuilder configures a Client beyond what Client::new offers: network settings for the
+/// default ReqwestTransport, headers and query parameters sent with every request, and the
+/// policies otherwise installed with the Client setters.
+///
+/// ```ignore
+/// let client = ClientBuilder::new(access_key)
+///     .connect_timeout(std::time::Duration::from_secs(5))
+///     .timeout(std::time::Duration::from_secs(30))
+///     .proxy("http://proxy.internal:3128")
+///     .user_agent("dashboard/1.0")
+///     .quota_user("user-1234")
+///     .build()?;
+/// ```
+#[derive(Debug)]
+pub struct ClientBuilder {
+    token: Arc<dyn TokenProvider>,
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    proxy: Option<String>,
+    user_agent: Option<String>,
+    headers: Vec<(String, String)>,
+    quota_user: Option<String>,
+    gzip: bool,
+    base_url: Option<String>,
+    plain_http: bool,
+    transport: Option<Arc<dyn Transport>>,
+    retry: Option<RetryPolicy>,
+    governor: Option<QuotaGovernor>,
+    concurrency: Option<ConcurrencyLimit>,
+    cache: Option<ResponseCache>,
+    dry_run: Option<DryRun>,
+    middleware: Vec<Arc<dyn Middleware>>,
+}
+
+impl ClientBuilder {
+    /// Start building a client which uses the access key.
+    pub fn new(access_key: String) -> Self {
+        Self::with_token_provider(StaticToken::new(access_key))
+    }
+
+    /// Start building a client which obtains its access tokens from the provider.
+    pub fn with_token_provider(provider: impl TokenProvider + 'static) -> Self {
+        Self {
+            token: Arc::new(provider),
+            connect_timeout: None,
+            timeout: None,
+            proxy: None,
+            user_agent: None,
+            headers: Vec::new(),
+            quota_user: None,
+            gzip: true,
+            base_url: None,
+            plain_http: false,
+            transport: None,
+            retry: None,
+            governor: None,
+            concurrency: None,
+            cache: None,
+            dry_run: None,
+            middleware: Vec::new(),
+        }
+    }
+
+    /// Give up on establishing a connection after the duration.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Give up on each attempt after the duration, from connecting until the body is read.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Send all requests through the HTTP or HTTPS proxy at the URL.
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    /// The User-Agent header sent with every request.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// A header sent with every request. Headers set for an individual request take precedence.
+    pub fn default_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Attribute requests to this user for quota purposes, by sending the quotaUser query
+    /// parameter with every request. Useful for services acting on behalf of many users.
+    pub fn quota_user(mut self, quota_user: impl Into<String>) -> Self {
+        self.quota_user = Some(quota_user.into());
+        self
+    }
+
+    /// Whether to request and decompress gzip encoded responses. Defaults to true.
+    pub fn gzip(mut self, gzip: bool) -> Self {
+        self.gzip = gzip;
+        self
+    }
+
+    /// See Client::set_base_url.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// See Client::allow_plain_http.
+    pub fn allow_plain_http(mut self) -> Self {
+        self.plain_http = true;
+        self
+    }
+
+    /// Send requests through the transport. The network settings of this builder only apply to
+    /// the default ReqwestTransport, and are ignored when a transport is provided.
+    pub fn transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// See Client::set_retry_policy.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// See Client::set_quota_governor.
+    pub fn quota_governor(mut self, governor: QuotaGovernor) -> Self {
+        self.governor = Some(governor);
+        self
+    }
+
+    /// See Client::set_concurrency_limit.
+    pub fn concurrency_limit(mut self, limit: ConcurrencyLimit) -> Self {
+        self.concurrency = Some(limit);
+        self
+    }
+
+    /// See Client::set_response_cache.
+    pub fn response_cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// See Client::set_dry_run.
+    pub fn dry_run(mut self, dry_run: DryRun) -> Self {
+        self.dry_run = Some(dry_run);
+        self
+    }
+
+    /// See Client::add_middleware.
+    pub fn middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    fn reqwest_transport(&self) -> Result<ReqwestTransport, ClientError> {
+        let mut builder = reqwest::ClientBuilder::new()
+            .gzip(self.gzip)
+            .https_only(!self.plain_http);
+
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+
+        Ok(ReqwestTransport::from_client(builder.build()?))
+    }
+
+    pub fn build(self) -> Result<Client, ClientError> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            headers.append(
+                HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|e| ClientError::UnknownError(e.to_string()))?,
+                HeaderValue::from_str(value)
+                    .map_err(|e| ClientError::UnknownError(e.to_string()))?,
+            );
+        }
+
+        let transport = match &self.transport {
+            Some(transport) => transport.clone(),
+            None => Arc::new(self.reqwest_transport()?),
+        };
+
+        let mut client = Client::from_parts(self.token, transport, self.middleware);
+        client.set_default_headers(headers);
+        client.set_quota_user(self.quota_user);
+
+        if let Some(base_url) = &self.base_url {
+            client.set_base_url(base_url)?;
+        }
+
+        if let Some(retry) = self.retry {
+            client.set_retry_policy(retry);
+        }
+
+        if let Some(governor) = self.governor {
+            client.set_quota_governor(governor);
+        }
+
+        if let Some(limit) = self.concurrency {
+            client.set_concurrency_limit(limit);
+        }
+
+        if let Some(cache) = self.cache {
+            client.set_response_cache(cache);
+        }
+
+        if let Some(dry_run) = self.dry_run {
+            client.set_dry_run(dry_run);
+        }
+
+        Ok(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{resources::CalendarList, transport::fake::FakeTransport};
+
+    #[tokio::test]
+    async fn applies_request_defaults() {
+        let transport = FakeTransport::new();
+        let client = ClientBuilder::new("token".to_string())
+            .transport(transport.clone())
+            .default_header("x-goog-user-project", "billing")
+            .default_header("x-overridden", "default")
+            .quota_user("user-1234")
+            .base_url("https://calendar.example.test/v3/")
+            .build()
+            .unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-overridden", HeaderValue::from_static("request"));
+        client
+            .request(reqwest::Method::GET, None, CalendarList::default(), headers)
+            .await
+            .unwrap();
+
+        let requests = transport.requests();
+        let req = &requests[0];
+        assert_eq!(req.url.host_str(), Some("calendar.example.test"));
+        assert!(req.url.path().starts_with("/v3/"));
+        assert!(req
+            .url
+            .query_pairs()
+            .any(|(k, v)| k == "quotaUser" && v == "user-1234"));
+        assert_eq!(req.headers["x-goog-user-project"], "billing");
+        assert_eq!(req.headers["x-overridden"], "request");
+        assert_eq!(req.headers["authorization"], "Bearer token");
+    }
+}
@@ -0,0 +1,448 @@
+use crate::{
+    client::ClientError,
+    resources::{derive_event_id, Event, EventClient, SendUpdates},
+};
+use futures::StreamExt;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::io::AsyncWriteExt;
+
+/// A single operation for the BulkExecutor. Each runs the EventClient method of the same name,
+/// except Insert, which runs EventClient::insert_idempotent.
+#[derive(Debug, Clone)]
+pub enum EventOperation {
+    Insert(Event),
+    Import(Event),
+    Update(Event),
+    Delete(Event),
+    Move {
+        event: Event,
+        destination: String,
+        send_updates: Option<SendUpdates>,
+    },
+}
+
+impl EventOperation {
+    /// Run the operation, returning the resulting event for those which produce one. Inserts use
+    /// EventClient::insert_idempotent, so an event without an ID is given one.
+    pub async fn run(self, client: &EventClient) -> Result<Option<Event>, ClientError> {
+        match self {
+            Self::Insert(event) => client.insert_idempotent(event, None).await.map(Some),
+            Self::Import(event) => client.import(event).await.map(Some),
+            Self::Update(event) => client.update(event).await.map(Some),
+            Self::Delete(event) => client.delete(event).await.map(|_| None),
+            Self::Move {
+                event,
+                destination,
+                send_updates,
+            } => client
+                .move_to_calendar(event, destination, send_updates)
+                .await
+                .map(|_| None),
+        }
+    }
+}
+
+/// The outcome of one operation, identified by its position in the input.
+#[derive(Debug, Clone)]
+pub struct BulkItem {
+    pub index: usize,
+    pub result: Result<Option<Event>, ClientError>,
+}
+
+/// BulkReport holds the results of a BulkExecutor run, ordered by input position. Operations
+/// skipped because the checkpoint recorded them as done have no result.
+#[derive(Debug, Clone, Default)]
+pub struct BulkReport {
+    pub items: Vec<BulkItem>,
+    pub skipped: usize,
+}
+
+impl BulkReport {
+    pub fn succeeded(&self) -> impl Iterator<Item = &BulkItem> {
+        self.items.iter().filter(|item| item.result.is_ok())
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &BulkItem> {
+        self.items.iter().filter(|item| item.result.is_err())
+    }
+}
+
+/// Progress is passed to the progress callback after every operation completes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Progress {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+impl Progress {
+    /// Operations which have not completed yet.
+    pub fn remaining(&self) -> usize {
+        self.total - self.succeeded - self.failed - self.skipped
+    }
+}
+
+type ProgressCallback = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+/// Checkpoint is an append-only file of the input positions which completed successfully,
+/// preceded by a random salt from which the IDs of inserted events are derived.
+#[derive(Debug)]
+struct Checkpoint {
+    salt: String,
+    done: HashSet<usize>,
+    file: tokio::fs::File,
+}
+
+fn to_error(e: std::io::Error) -> ClientError {
+    ClientError::UnknownError(e.to_string())
+}
+
+impl Checkpoint {
+    async fn open(path: &Path) -> Result<Self, ClientError> {
+        let contents = match tokio::fs::read_to_string(path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(to_error(e)),
+        };
+
+        let mut salt = None;
+        let mut done = HashSet::new();
+        for line in contents.lines() {
+            match line.trim().strip_prefix("salt ") {
+                Some(s) if salt.is_none() => salt = Some(s.to_string()),
+                // a partially written last line from a crash is ignored.
+                _ => done.extend(line.trim().parse::<usize>().ok()),
+            }
+        }
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .map_err(to_error)?;
+
+        let salt = match salt {
+            Some(salt) => salt,
+            None => {
+                let salt = uuid::Uuid::new_v4().simple().to_string();
+                // a crash may have left a partial line behind; start the salt on its own.
+                let sep = if contents.is_empty() || contents.ends_with('\n') {
+                    ""
+                } else {
+                    "\n"
+                };
+                file.write_all(format!("{}salt {}\n", sep, salt).as_bytes())
+                    .await
+                    .map_err(to_error)?;
+                file.flush().await.map_err(to_error)?;
+                salt
+            }
+        };
+
+        Ok(Self { salt, done, file })
+    }
+
+    async fn record(&mut self, index: usize) -> Result<(), ClientError> {
+        self.file
+            .write_all(format!("{}\n", index).as_bytes())
+            .await
+            .map_err(to_error)?;
+        self.file.flush().await.map_err(to_error)
+    }
+
+    /// Give an event to be inserted an ID derived from the checkpoint salt and its position, so
+    /// that an insert which succeeded before a crash, but was not recorded, is not repeated on
+    /// resume.
+    fn assign_id(&self, index: usize, op: &mut EventOperation) {
+        if let EventOperation::Insert(event) = op {
+            if event.id.is_none() {
+                event.id = Some(derive_event_id(format!("{}#{}", self.salt, index)));
+            }
+        }
+    }
+}
+
+/// BulkExecutor runs many event operations with bounded parallelism on a shared client. Combine
+/// it with a RetryPolicy and QuotaGovernor on the Client to stay within Google's quotas.
+///
+/// With a checkpoint file, each successful operation is recorded by its position in the input,
+/// and a later run over the same input skips them; failed operations are attempted again. The
+/// input must be provided in the same order for a checkpoint to be meaningful. Events inserted
+/// without an ID are given one derived from a random salt stored in the checkpoint and their
+/// position, so a checkpoint file must not be reused for a different input.
+#[derive(Clone)]
+pub struct BulkExecutor {
+    client: EventClient,
+    concurrency: usize,
+    progress: Option<ProgressCallback>,
+    checkpoint: Option<PathBuf>,
+}
+
+impl std::fmt::Debug for BulkExecutor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BulkExecutor")
+            .field("concurrency", &self.concurrency)
+            .field("checkpoint", &self.checkpoint)
+            .finish()
+    }
+}
+
+impl BulkExecutor {
+    /// Run operations through the client, four at a time.
+    pub fn new(client: EventClient) -> Self {
+        Self {
+            client,
+            concurrency: 4,
+            progress: None,
+            checkpoint: None,
+        }
+    }
+
+    /// The maximum number of operations in flight at once.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Call the function after each operation completes.
+    pub fn on_progress(mut self, progress: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    /// Record completed operations to, and resume from, the file.
+    pub fn checkpoint(mut self, path: impl AsRef<Path>) -> Self {
+        self.checkpoint = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Run the operations. Failures of individual operations are reported in the BulkReport; an
+    /// error is only returned if the checkpoint could not be read or written.
+    pub async fn run(
+        &self,
+        operations: impl IntoIterator<Item = EventOperation>,
+    ) -> Result<BulkReport, ClientError> {
+        let mut checkpoint = match &self.checkpoint {
+            Some(path) => Some(Checkpoint::open(path).await?),
+            None => None,
+        };
+
+        let operations = operations.into_iter().collect::<Vec<_>>();
+        let mut progress = Progress {
+            total: operations.len(),
+            ..Default::default()
+        };
+
+        let pending = operations
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| {
+                checkpoint
+                    .as_ref()
+                    .is_none_or(|checkpoint| !checkpoint.done.contains(index))
+            })
+            .map(|(index, mut op)| {
+                if let Some(checkpoint) = &checkpoint {
+                    checkpoint.assign_id(index, &mut op);
+                }
+                (index, op)
+            })
+            .collect::<Vec<_>>();
+
+        progress.skipped = progress.total - pending.len();
+        self.report(&progress);
+
+        let mut results = futures::stream::iter(pending)
+            .map(|(index, op)| {
+                let client = self.client.clone();
+                async move {
+                    BulkItem {
+                        index,
+                        result: op.run(&client).await,
+                    }
+                }
+            })
+            .buffer_unordered(self.concurrency);
+
+        let mut report = BulkReport {
+            items: Vec::with_capacity(progress.total - progress.skipped),
+            skipped: progress.skipped,
+        };
+
+        while let Some(item) = results.next().await {
+            if item.result.is_ok() {
+                if let Some(checkpoint) = &mut checkpoint {
+                    checkpoint.record(item.index).await?;
+                }
+                progress.succeeded += 1;
+            } else {
+                progress.failed += 1;
+            }
+
+            self.report(&progress);
+            report.items.push(item);
+        }
+
+        report.items.sort_by_key(|item| item.index);
+        Ok(report)
+    }
+
+    fn report(&self, progress: &Progress) {
+        if let Some(callback) = &self.progress {
+            callback(progress)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::Client, transport::fake::FakeTransport};
+    use reqwest::StatusCode;
+    use std::sync::Mutex;
+
+    const NOT_FOUND: &str = r#"{"error":{"code":404,"message":"Not Found","errors":[]}}"#;
+
+    fn event(id: Option<&str>) -> Event {
+        let mut event = Event::default();
+        event.calendar_id = Some("primary".to_string());
+        event.id = id.map(ToString::to_string);
+        event
+    }
+
+    fn executor(transport: &FakeTransport) -> BulkExecutor {
+        let mut client = Client::new("token".to_string()).unwrap();
+        client.set_transport(transport.clone());
+        // one at a time, so responses are matched to operations in order.
+        BulkExecutor::new(EventClient::new(client)).concurrency(1)
+    }
+
+    fn checkpoint_path() -> PathBuf {
+        std::env::temp_dir().join(format!("gcal-bulk-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn deletes() -> Vec<EventOperation> {
+        ["e0", "e1", "e2"]
+            .into_iter()
+            .map(|id| EventOperation::Delete(event(Some(id))))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn resumes_failed_operations() {
+        let path = checkpoint_path();
+        let transport = FakeTransport::new();
+        transport.respond_json(StatusCode::NO_CONTENT, "");
+        transport.respond_json(StatusCode::NOT_FOUND, NOT_FOUND);
+        transport.respond_json(StatusCode::NO_CONTENT, "");
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let progress = seen.clone();
+        let report = executor(&transport)
+            .checkpoint(&path)
+            .on_progress(move |p| progress.lock().unwrap().push(*p))
+            .run(deletes())
+            .await
+            .unwrap();
+
+        assert_eq!(report.skipped, 0);
+        assert_eq!(
+            report.failed().map(|item| item.index).collect::<Vec<_>>(),
+            vec![1]
+        );
+
+        let seen = seen.lock().unwrap().clone();
+        assert_eq!(seen.len(), 4);
+        assert_eq!(seen[0].remaining(), 3);
+        let last = seen.last().unwrap();
+        assert_eq!((last.succeeded, last.failed, last.remaining()), (2, 1, 0));
+
+        // the second run only attempts the operation which failed.
+        transport.respond_json(StatusCode::NO_CONTENT, "");
+        let report = executor(&transport)
+            .checkpoint(&path)
+            .run(deletes())
+            .await
+            .unwrap();
+
+        assert_eq!(report.skipped, 2);
+        assert_eq!(report.items.len(), 1);
+        assert_eq!(report.items[0].index, 1);
+        assert!(report.items[0].result.is_ok());
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 4);
+        assert!(requests[3].url.path().ends_with("/events/e1"));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn skips_everything_once_done() {
+        let path = checkpoint_path();
+        let transport = FakeTransport::new();
+
+        executor(&transport)
+            .checkpoint(&path)
+            .run(deletes())
+            .await
+            .unwrap();
+
+        let report = executor(&transport)
+            .checkpoint(&path)
+            .run(deletes())
+            .await
+            .unwrap();
+
+        assert_eq!(report.skipped, 3);
+        assert!(report.items.is_empty());
+        assert_eq!(transport.requests().len(), 3);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn insert_ids_do_not_depend_on_the_path_spelling() {
+        let path = checkpoint_path();
+        let other = path
+            .parent()
+            .unwrap()
+            .join(".")
+            .join(path.file_name().unwrap());
+
+        let mut first = EventOperation::Insert(event(None));
+        Checkpoint::open(&path)
+            .await
+            .unwrap()
+            .assign_id(7, &mut first);
+        let mut second = EventOperation::Insert(event(None));
+        Checkpoint::open(&other)
+            .await
+            .unwrap()
+            .assign_id(7, &mut second);
+
+        let id = |op: &EventOperation| match op {
+            EventOperation::Insert(event) => event.id.clone().unwrap(),
+            _ => unreachable!(),
+        };
+        assert_eq!(id(&first), id(&second));
+
+        // a new checkpoint gets a new salt, so its IDs differ.
+        let fresh = checkpoint_path();
+        let mut third = EventOperation::Insert(event(None));
+        Checkpoint::open(&fresh)
+            .await
+            .unwrap()
+            .assign_id(7, &mut third);
+        assert_ne!(id(&first), id(&third));
+
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(fresh).unwrap();
+    }
+}
@@ -0,0 +1,360 @@
+use crate::{client::ClientError, token::TokenProvider, transport::HttpResponse};
+use async_trait::async_trait;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, ETAG},
+    StatusCode,
+};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use url::Url;
+
+/// Response headers which no longer apply to a cached body.
+const DROPPED_HEADERS: &[&str] = &[
+    "content-encoding",
+    "content-length",
+    "transfer-encoding",
+    "set-cookie",
+];
+
+/// FNV-1a, a small stable hash used for cache keys and file names.
+pub(crate) fn fnv1a(input: &str) -> u64 {
+    input.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// A successful GET response along with the ETag used to revalidate it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub etag: String,
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: String,
+}
+
+impl CachedResponse {
+    /// Capture the response, if it carries an ETag and can therefore be revalidated.
+    pub(crate) fn from_response(resp: &HttpResponse) -> Option<Self> {
+        let etag = resp.headers().get(ETAG)?.to_str().ok()?.to_string();
+
+        Some(Self {
+            etag,
+            status: resp.status().as_u16(),
+            headers: resp
+                .headers()
+                .iter()
+                .filter(|(name, _)| !DROPPED_HEADERS.contains(&name.as_str()))
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect(),
+            body: resp.text(),
+        })
+    }
+
+    pub(crate) fn to_response(&self) -> HttpResponse {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.append(name, value);
+            }
+        }
+
+        HttpResponse {
+            status: StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK),
+            headers,
+            body: self.body.clone().into_bytes(),
+        }
+    }
+}
+
+/// CacheStore holds cached responses for a ResponseCache. Stores are best-effort: a failure to
+/// read an entry is treated as a miss, and a failure to write one is ignored.
+#[async_trait]
+pub trait CacheStore: std::fmt::Debug + Send + Sync {
+    async fn get(&self, key: &str) -> Option<CachedResponse>;
+    async fn put(&self, key: &str, entry: CachedResponse);
+    async fn remove(&self, key: &str);
+}
+
+#[derive(Debug, Default)]
+struct Lru {
+    entries: HashMap<String, (u64, CachedResponse)>,
+    order: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl Lru {
+    fn touch(&mut self, key: &str) {
+        self.tick += 1;
+        let tick = self.tick;
+
+        if let Some((used, _)) = self.entries.get_mut(key) {
+            self.order.remove(used);
+            *used = tick;
+            self.order.insert(tick, key.to_string());
+        }
+    }
+}
+
+/// MemoryCache keeps up to `capacity` responses in memory, evicting the least recently used.
+#[derive(Debug, Clone)]
+pub struct MemoryCache {
+    capacity: usize,
+    lru: Arc<Mutex<Lru>>,
+}
+
+impl MemoryCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            lru: Default::default(),
+        }
+    }
+
+    /// The number of cached responses.
+    pub fn len(&self) -> usize {
+        self.lru.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl CacheStore for MemoryCache {
+    async fn get(&self, key: &str) -> Option<CachedResponse> {
+        let mut lru = self.lru.lock().unwrap();
+        lru.touch(key);
+        lru.entries.get(key).map(|(_, entry)| entry.clone())
+    }
+
+    async fn put(&self, key: &str, entry: CachedResponse) {
+        let mut lru = self.lru.lock().unwrap();
+        lru.tick += 1;
+        let tick = lru.tick;
+
+        if let Some((used, _)) = lru.entries.insert(key.to_string(), (tick, entry)) {
+            lru.order.remove(&used);
+        }
+        lru.order.insert(tick, key.to_string());
+
+        while lru.entries.len() > self.capacity {
+            match lru.order.pop_first() {
+                Some((_, oldest)) => lru.entries.remove(&oldest),
+                None => break,
+            };
+        }
+    }
+
+    async fn remove(&self, key: &str) {
+        let mut lru = self.lru.lock().unwrap();
+        if let Some((used, _)) = lru.entries.remove(key) {
+            lru.order.remove(&used);
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DiskEntry {
+    key: String,
+    entry: CachedResponse,
+}
+
+/// DiskCache keeps one JSON file per response in a directory, so the cache survives restarts
+/// and may be shared by processes polling the same calendars.
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    /// Use the directory, creating it if necessary.
+    pub fn new(dir: impl AsRef<Path>) -> Result<Self, ClientError> {
+        std::fs::create_dir_all(dir.as_ref())
+            .map_err(|e| ClientError::UnknownError(e.to_string()))?;
+
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", fnv1a(key)))
+    }
+}
+
+#[async_trait]
+impl CacheStore for DiskCache {
+    async fn get(&self, key: &str) -> Option<CachedResponse> {
+        let contents = tokio::fs::read(self.path(key)).await.ok()?;
+        let file: DiskEntry = serde_json::from_slice(&contents).ok()?;
+
+        // file names are hashed, so make sure this is not a colliding key.
+        (file.key == key).then_some(file.entry)
+    }
+
+    async fn put(&self, key: &str, entry: CachedResponse) {
+        let file = DiskEntry {
+            key: key.to_string(),
+            entry,
+        };
+
+        if let Ok(contents) = serde_json::to_vec(&file) {
+            let path = self.path(key);
+            // write and rename so concurrent readers never see a partial file.
+            let tmp = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4()));
+            if tokio::fs::write(&tmp, contents).await.is_ok()
+                && tokio::fs::rename(&tmp, &path).await.is_err()
+            {
+                let _ = tokio::fs::remove_file(&tmp).await;
+            }
+        }
+    }
+
+    async fn remove(&self, key: &str) {
+        let _ = tokio::fs::remove_file(self.path(key)).await;
+    }
+}
+
+/// ResponseCache caches GET responses which carry an ETag. Cached responses are always
+/// revalidated with If-None-Match; when the API answers 304 Not Modified the cached body is
+/// returned in its place, saving quota and transfer. Entries are keyed by the URL and the access
+/// identity, so clients for different users never see each other's responses. Install it with
+/// Client::set_response_cache.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    store: Arc<dyn CacheStore>,
+    identity: Option<String>,
+}
+
+impl ResponseCache {
+    pub fn new(store: impl CacheStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+            identity: None,
+        }
+    }
+
+    /// A cache kept in memory, holding up to `capacity` responses.
+    pub fn memory(capacity: usize) -> Self {
+        Self::new(MemoryCache::new(capacity))
+    }
+
+    /// Key entries by this identity, such as the user's email address, instead of a hash of the
+    /// access token. Set this when tokens are refreshed, or the cache will be emptied each time
+    /// the token changes.
+    pub fn with_identity(mut self, identity: impl Into<String>) -> Self {
+        self.identity = Some(identity.into());
+        self
+    }
+
+    pub fn store(&self) -> &Arc<dyn CacheStore> {
+        &self.store
+    }
+
+    pub(crate) async fn key(
+        &self,
+        token: &dyn TokenProvider,
+        url: &Url,
+    ) -> Result<String, ClientError> {
+        let identity = match &self.identity {
+            Some(identity) => identity.clone(),
+            None => format!("{:016x}", fnv1a(&token.token().await?)),
+        };
+
+        Ok(format!("{} {}", identity, url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::Client, resources::CalendarList, transport::fake::FakeTransport};
+    use reqwest::header::IF_NONE_MATCH;
+
+    fn entry(body: &str) -> CachedResponse {
+        CachedResponse {
+            etag: format!("\"{}\"", body),
+            status: 200,
+            headers: BTreeMap::new(),
+            body: body.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn memory_cache_evicts_least_recently_used() {
+        let cache = MemoryCache::new(2);
+        cache.put("a", entry("a")).await;
+        cache.put("b", entry("b")).await;
+
+        // using a makes b the oldest.
+        assert!(cache.get("a").await.is_some());
+        cache.put("c", entry("c")).await;
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("a").await.is_some());
+        assert!(cache.get("b").await.is_none());
+        assert!(cache.get("c").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn disk_cache_round_trip() {
+        let dir = std::env::temp_dir().join(format!("gcal-cache-{}", uuid::Uuid::new_v4()));
+        let cache = DiskCache::new(&dir).unwrap();
+
+        cache.put("key", entry("body")).await;
+        assert_eq!(cache.get("key").await.unwrap().body, "body");
+
+        cache.remove("key").await;
+        assert!(cache.get("key").await.is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn disk_cache_rejects_colliding_keys() {
+        let dir = std::env::temp_dir().join(format!("gcal-cache-{}", uuid::Uuid::new_v4()));
+        let cache = DiskCache::new(&dir).unwrap();
+
+        // stand in for a hash collision by putting one key's file where the other's would be.
+        cache.put("first", entry("first")).await;
+        std::fs::rename(cache.path("first"), cache.path("second")).unwrap();
+        assert!(cache.get("second").await.is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn revalidates_cached_responses() {
+        let transport = FakeTransport::new();
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+        transport.respond(StatusCode::OK, headers, r#"{"items":[]}"#);
+        transport.respond(StatusCode::NOT_MODIFIED, HeaderMap::new(), "");
+
+        let mut client = Client::new("token".to_string()).unwrap();
+        client.set_transport(transport.clone());
+        client.set_response_cache(ResponseCache::memory(10));
+
+        let first = client.get(None, CalendarList::default()).await.unwrap();
+        let second = client.get(None, CalendarList::default()).await.unwrap();
+
+        assert_eq!(second.status(), StatusCode::OK);
+        assert_eq!(second.text(), first.text());
+
+        let requests = transport.requests();
+        assert!(!requests[0].headers.contains_key(IF_NONE_MATCH));
+        assert_eq!(requests[1].headers[IF_NONE_MATCH], "\"v1\"");
+    }
+}
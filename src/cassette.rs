@@ -0,0 +1,338 @@
+use crate::{
+    batch::response_boundary,
+    client::ClientError,
+    middleware::redact_url,
+    transport::{HttpRequest, HttpResponse, Transport, TransportError, TransportErrorKind},
+};
+use async_trait::async_trait;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    StatusCode,
+};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Headers which are never written to a cassette.
+const SCRUBBED_HEADERS: &[&str] = &["authorization", "cookie", "set-cookie"];
+
+/// Batch requests use a random multipart boundary, which is replaced by this one when recording
+/// and matching so that a recorded batch matches the same batch sent again.
+const CASSETTE_BOUNDARY: &str = "batch_cassette";
+
+/// Response headers which no longer apply once the body has been decoded and stored.
+const DROPPED_HEADERS: &[&str] = &["content-encoding", "content-length", "transfer-encoding"];
+
+/// A recorded request. Requests are matched on method, path, query and body; the headers are
+/// kept for reference only.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    #[serde(default)]
+    pub query: Vec<(String, String)>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Send requests to the API and write every interaction to the cassette file.
+    Record,
+    /// Answer requests from the cassette file without touching the network.
+    Replay,
+}
+
+#[derive(Debug, Default)]
+struct Tape {
+    interactions: Vec<Interaction>,
+    played: Vec<bool>,
+}
+
+/// Cassette records request/response pairs to a JSON fixture while running against the real API,
+/// and replays them later for deterministic, offline tests. Authorization headers and credential
+/// query parameters are scrubbed when recording. Install it with Client::set_cassette.
+#[derive(Debug, Clone)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    tape: Arc<Mutex<Tape>>,
+}
+
+impl Cassette {
+    /// Start recording to the file, replacing any existing contents.
+    pub fn record(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            mode: CassetteMode::Record,
+            tape: Default::default(),
+        }
+    }
+
+    /// Load a previously recorded file for replay.
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self, ClientError> {
+        let file: CassetteFile = serde_json::from_slice(
+            &std::fs::read(path.as_ref()).map_err(|e| ClientError::UnknownError(e.to_string()))?,
+        )?;
+
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            mode: CassetteMode::Replay,
+            tape: Arc::new(Mutex::new(Tape {
+                played: vec![false; file.interactions.len()],
+                interactions: file.interactions,
+            })),
+        })
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    /// The interactions recorded or loaded so far.
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.tape.lock().unwrap().interactions.clone()
+    }
+
+    fn save(&self, tape: &Tape) -> Result<(), ClientError> {
+        let file = CassetteFile {
+            interactions: tape.interactions.clone(),
+        };
+
+        std::fs::write(&self.path, serde_json::to_vec_pretty(&file)?)
+            .map_err(|e| ClientError::UnknownError(e.to_string()))
+    }
+
+    /// Capture the request as it will be stored and matched.
+    fn snapshot(req: &HttpRequest) -> RecordedRequest {
+        // redact_url scrubs credentials from the query string.
+        let url = url::Url::parse(&redact_url(&req.url)).unwrap_or_else(|_| req.url.clone());
+
+        let mut query = url
+            .query_pairs()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<Vec<_>>();
+        query.sort();
+
+        let mut headers = header_map(&req.headers, SCRUBBED_HEADERS);
+        let mut body = req
+            .body
+            .as_ref()
+            .map(|b| String::from_utf8_lossy(b).to_string());
+
+        let boundary = headers
+            .get("content-type")
+            .filter(|ct| ct.starts_with("multipart/"))
+            .and_then(|ct| response_boundary(ct));
+
+        if let Some(boundary) = boundary {
+            if let Some(ct) = headers.get_mut("content-type") {
+                *ct = ct.replace(&boundary, CASSETTE_BOUNDARY);
+            }
+            body = body.map(|b| {
+                b.replace(
+                    &format!("--{}", boundary),
+                    &format!("--{}", CASSETTE_BOUNDARY),
+                )
+            });
+        }
+
+        RecordedRequest {
+            method: req.method.to_string(),
+            path: url.path().to_string(),
+            query,
+            headers,
+            body,
+        }
+    }
+
+    /// Store the interaction.
+    fn store(&self, request: RecordedRequest, resp: &HttpResponse) -> Result<(), ClientError> {
+        let response = RecordedResponse {
+            status: resp.status.as_u16(),
+            headers: header_map(&resp.headers, &[SCRUBBED_HEADERS, DROPPED_HEADERS].concat()),
+            body: resp.text(),
+        };
+
+        let mut tape = self.tape.lock().unwrap();
+        tape.interactions.push(Interaction { request, response });
+        tape.played.push(true);
+        self.save(&tape)
+    }
+
+    /// Find the recorded response for the request. Interactions are replayed in order, so
+    /// repeated identical requests receive successive responses; once they are exhausted the
+    /// last one is repeated.
+    fn play(&self, request: &RecordedRequest) -> Result<HttpResponse, ClientError> {
+        let mut tape = self.tape.lock().unwrap();
+
+        let matching = tape
+            .interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| matches(&i.request, request))
+            .map(|(n, _)| n)
+            .collect::<Vec<_>>();
+
+        let index = matching
+            .iter()
+            .find(|n| !tape.played[**n])
+            .or_else(|| matching.last())
+            .copied()
+            .ok_or_else(|| {
+                ClientError::UnknownError(format!(
+                    "no recorded interaction for {} {}",
+                    request.method, request.path
+                ))
+            })?;
+
+        tape.played[index] = true;
+        build_response(&tape.interactions[index].response)
+    }
+}
+
+fn matches(recorded: &RecordedRequest, request: &RecordedRequest) -> bool {
+    recorded.method == request.method
+        && recorded.path == request.path
+        && recorded.query == request.query
+        && bodies_match(recorded.body.as_deref(), request.body.as_deref())
+}
+
+/// JSON bodies are compared structurally so that field order does not matter.
+fn bodies_match(recorded: Option<&str>, request: Option<&str>) -> bool {
+    match (recorded, request) {
+        (Some(a), Some(b)) => {
+            match (
+                serde_json::from_str::<serde_json::Value>(a),
+                serde_json::from_str::<serde_json::Value>(b),
+            ) {
+                (Ok(a), Ok(b)) => a == b,
+                _ => a == b,
+            }
+        }
+        (a, b) => a.unwrap_or_default().is_empty() && b.unwrap_or_default().is_empty(),
+    }
+}
+
+fn header_map(headers: &reqwest::header::HeaderMap, skip: &[&str]) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .filter(|(name, _)| !skip.contains(&name.as_str()))
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+fn build_response(recorded: &RecordedResponse) -> Result<HttpResponse, ClientError> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &recorded.headers {
+        headers.append(
+            HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| ClientError::UnknownError(e.to_string()))?,
+            HeaderValue::from_str(value).map_err(|e| ClientError::UnknownError(e.to_string()))?,
+        );
+    }
+
+    Ok(HttpResponse {
+        status: StatusCode::from_u16(recorded.status)
+            .map_err(|e| ClientError::UnknownError(e.to_string()))?,
+        headers,
+        body: recorded.body.clone().into_bytes(),
+    })
+}
+
+/// CassetteTransport wraps another transport, recording what it returns or replaying responses
+/// in its place. Client::set_cassette installs it.
+#[derive(Debug, Clone)]
+pub struct CassetteTransport {
+    cassette: Cassette,
+    inner: Arc<dyn Transport>,
+}
+
+impl CassetteTransport {
+    pub fn new(cassette: Cassette, inner: Arc<dyn Transport>) -> Self {
+        Self { cassette, inner }
+    }
+}
+
+#[async_trait]
+impl Transport for CassetteTransport {
+    async fn execute(&self, req: HttpRequest) -> Result<HttpResponse, TransportError> {
+        let recorded = Cassette::snapshot(&req);
+        let to_transport =
+            |e: ClientError| TransportError::new(TransportErrorKind::Other, e.to_string());
+
+        match self.cassette.mode() {
+            CassetteMode::Replay => self.cassette.play(&recorded).map_err(to_transport),
+            CassetteMode::Record => {
+                let resp = self.inner.execute(req).await?;
+                self.cassette.store(recorded, &resp).map_err(to_transport)?;
+                Ok(resp)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::{header::CONTENT_TYPE, Method};
+
+    fn batch_request(boundary: &str) -> HttpRequest {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_str(&format!("multipart/mixed; boundary={}", boundary)).unwrap(),
+        );
+
+        HttpRequest {
+            method: Method::POST,
+            url: url::Url::parse("https://example.com/batch/calendar/v3").unwrap(),
+            headers,
+            body: Some(
+                format!(
+                    "--{0}\r\nContent-Type: application/http\r\n\r\nGET /calendar/v3/calendars/primary HTTP/1.1\r\n\r\n--{0}--\r\n",
+                    boundary
+                )
+                .into_bytes(),
+            ),
+        }
+    }
+
+    #[test]
+    fn batch_boundaries_are_normalized() {
+        let recorded = Cassette::snapshot(&batch_request("batch_aaaa"));
+        let replayed = Cassette::snapshot(&batch_request("batch_bbbb"));
+
+        assert!(matches(&recorded, &replayed));
+        assert!(!recorded.body.as_deref().unwrap().contains("batch_aaaa"));
+        assert_eq!(
+            recorded.headers.get("content-type").map(String::as_str),
+            Some("multipart/mixed; boundary=batch_cassette")
+        );
+    }
+}
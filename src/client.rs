@@ -1,20 +1,239 @@
-use crate::sendable::Sendable;
+use crate::{
+    batch::Batch,
+    cache::{CachedResponse, ResponseCache},
+    cassette::{Cassette, CassetteTransport},
+    dryrun::DryRun,
+    middleware::{LoggingMiddleware, Middleware, RequestContext},
+    pool::Lease,
+    ratelimit::{ConcurrencyLimit, QuotaGovernor},
+    retry::{retry_after, retryable_error, retryable_transport_error, RetryPolicy},
+    sendable::{Sendable, BASE_URL},
+    token::{StaticToken, TokenProvider},
+    transport::{HttpRequest, HttpResponse, ReqwestTransport, Transport, TransportError},
+};
 use reqwest::{
-    header::{HeaderMap, HeaderValue},
-    ClientBuilder, RequestBuilder, Response,
+    header::{
+        HeaderMap, HeaderValue, AUTHORIZATION, ETAG, IF_MATCH, IF_NONE_MATCH, WWW_AUTHENTICATE,
+    },
+    Method, StatusCode,
+};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 
-/// ClientError provides a mechanism to determine when the access token has expired. All other
-/// errors will be encapsulated by UnknownError.
+/// ClientError provides a mechanism to determine when the access token has expired. Errors
+/// returned by the Google API are parsed into the variants below, each carrying the ApiError
+/// payload; all other errors will be encapsulated by UnknownError.
 #[derive(Clone, Debug, Error)]
 pub enum ClientError {
     #[error("Invalid Access Token")]
     InvalidToken,
+    #[error("Bad Request: {0}")]
+    BadRequest(ApiError),
+    #[error("Forbidden: {0}")]
+    Forbidden(ApiError),
+    #[error("Not Found: {0}")]
+    NotFound(ApiError),
+    #[error("Conflict: {0}")]
+    Conflict(ApiError),
+    #[error("Gone: {0}")]
+    Gone(ApiError),
+    #[error("Precondition Failed: {0}")]
+    PreconditionFailed(ApiError),
+    #[error("Rate Limited: {0}")]
+    RateLimited(ApiError),
+    #[error("Quota Exceeded: {0}")]
+    QuotaExceeded(ApiError),
+    #[error("API Error: {0}")]
+    ApiError(ApiError),
+    #[error("Transport Error: {0}")]
+    Transport(TransportError),
+    #[error("Decode Error: {0}")]
+    Decode(DecodeError),
+    #[error("Invalid Event ID: {0}")]
+    InvalidEventId(String),
     #[error("Unknown Error: {0}")]
     UnknownError(String),
 }
 
+impl ClientError {
+    /// Classify an error response from the Google API. The body is expected to be Google's JSON
+    /// error payload, but anything else is preserved in ApiError.body. Any 401, or a challenge
+    /// naming invalid_token, is InvalidToken.
+    pub fn from_response(status: StatusCode, headers: &HeaderMap, body: String) -> Self {
+        let invalid_token = headers.get_all(WWW_AUTHENTICATE).iter().any(|h| {
+            h.to_str()
+                .unwrap_or_default()
+                .contains(r#"error="invalid_token""#)
+        });
+
+        if status == StatusCode::UNAUTHORIZED || invalid_token {
+            return Self::InvalidToken;
+        }
+
+        let err = ApiError::parse(status, body);
+
+        match status {
+            StatusCode::BAD_REQUEST => Self::BadRequest(err),
+            StatusCode::NOT_FOUND => Self::NotFound(err),
+            StatusCode::CONFLICT => Self::Conflict(err),
+            StatusCode::GONE => Self::Gone(err),
+            StatusCode::PRECONDITION_FAILED => Self::PreconditionFailed(err),
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited(err),
+            StatusCode::FORBIDDEN if err.is_rate_limited() => Self::RateLimited(err),
+            StatusCode::FORBIDDEN if err.is_quota_exceeded() => Self::QuotaExceeded(err),
+            StatusCode::FORBIDDEN => Self::Forbidden(err),
+            _ => Self::ApiError(err),
+        }
+    }
+
+    /// The parsed Google error, if this error came from an API response.
+    pub fn api_error(&self) -> Option<&ApiError> {
+        match self {
+            Self::BadRequest(err)
+            | Self::Forbidden(err)
+            | Self::NotFound(err)
+            | Self::Conflict(err)
+            | Self::Gone(err)
+            | Self::PreconditionFailed(err)
+            | Self::RateLimited(err)
+            | Self::QuotaExceeded(err)
+            | Self::ApiError(err) => Some(err),
+            Self::InvalidToken
+            | Self::Transport(_)
+            | Self::Decode(_)
+            | Self::InvalidEventId(_)
+            | Self::UnknownError(_) => None,
+        }
+    }
+
+    /// The HTTP status of the response which produced this error, if any.
+    pub fn status(&self) -> Option<StatusCode> {
+        self.api_error().map(|err| err.status)
+    }
+}
+
+/// ApiError is Google's error payload: `{"error":{"code","message","errors":[...]}}`. The HTTP
+/// status and raw response body are kept alongside it for logging.
+#[derive(Clone, Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+    pub errors: Vec<ApiErrorDetail>,
+    pub body: String,
+}
+
+/// DecodeError describes a response body which could not be deserialized into the expected
+/// type. path is the location of the offending value, e.g. `items[3].start.dateTime`, and
+/// snippet is the part of the body surrounding it.
+#[derive(Clone, Debug)]
+pub struct DecodeError {
+    pub path: String,
+    pub message: String,
+    pub snippet: String,
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "at {}: {} near `{}`",
+            self.path, self.message, self.snippet
+        )
+    }
+}
+
+/// A single entry of the `errors` array in Google's error payload. location and location_type
+/// name the offending field or parameter for BadRequest errors.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiErrorDetail {
+    #[serde(default)]
+    pub domain: String,
+    #[serde(default)]
+    pub reason: String,
+    #[serde(default)]
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiErrorEnvelope {
+    error: ApiErrorBody,
+}
+
+#[derive(Deserialize)]
+struct ApiErrorBody {
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    errors: Vec<ApiErrorDetail>,
+}
+
+impl ApiError {
+    fn parse(status: StatusCode, body: String) -> Self {
+        match serde_json::from_str::<ApiErrorEnvelope>(&body) {
+            Ok(envelope) => Self {
+                status,
+                message: envelope.error.message,
+                errors: envelope.error.errors,
+                body,
+            },
+            Err(_) => Self {
+                status,
+                message: body.clone(),
+                errors: Vec::new(),
+                body,
+            },
+        }
+    }
+
+    /// The reasons given for this error, e.g. `notFound` or `rateLimitExceeded`.
+    pub fn reasons(&self) -> impl Iterator<Item = &str> {
+        self.errors.iter().map(|e| e.reason.as_str())
+    }
+
+    /// The fields or parameters this error refers to, for validation failures.
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.errors.iter().filter_map(|e| e.location.as_deref())
+    }
+
+    pub fn is_rate_limited(&self) -> bool {
+        self.reasons()
+            .any(|r| r == "rateLimitExceeded" || r == "userRateLimitExceeded")
+    }
+
+    pub fn is_quota_exceeded(&self) -> bool {
+        self.reasons()
+            .any(|r| r == "quotaExceeded" || r == "dailyLimitExceeded")
+    }
+
+    /// Returns true if the token lacks the OAuth scope required for the request.
+    pub fn is_insufficient_scope(&self) -> bool {
+        self.reasons()
+            .any(|r| r == "insufficientPermissions" || r == "ACCESS_TOKEN_SCOPE_INSUFFICIENT")
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.status.as_u16(), self.message)?;
+
+        let reasons = self.reasons().collect::<Vec<_>>();
+        if !reasons.is_empty() {
+            write!(f, " ({})", reasons.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
 impl From<davisjr::errors::Error> for ClientError {
     fn from(value: davisjr::errors::Error) -> Self {
         Self::UnknownError(value.to_string())
@@ -45,86 +264,566 @@ impl From<reqwest::Error> for ClientError {
     }
 }
 
+impl From<TransportError> for ClientError {
+    fn from(value: TransportError) -> Self {
+        Self::Transport(value)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for ClientError {
+    fn from(value: jsonwebtoken::errors::Error) -> Self {
+        Self::UnknownError(value.to_string())
+    }
+}
+
 impl From<reqwest::header::ToStrError> for ClientError {
     fn from(value: reqwest::header::ToStrError) -> Self {
         Self::UnknownError(value.to_string())
     }
 }
 
+/// Precondition makes a request conditional on the ETag of the resource. A failed IfMatch is
+/// reported as ClientError::PreconditionFailed; a satisfied IfNoneMatch yields a 304 response.
+#[derive(Debug, Clone)]
+pub enum Precondition {
+    /// Only apply the request if the resource still has this ETag.
+    IfMatch(String),
+    /// Only return the resource if its ETag differs from this one.
+    IfNoneMatch(String),
+}
+
+impl Precondition {
+    pub fn headers(&self) -> Result<HeaderMap, ClientError> {
+        let (name, etag) = match self {
+            Self::IfMatch(etag) => (IF_MATCH, etag),
+            Self::IfNoneMatch(etag) => (IF_NONE_MATCH, etag),
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            name,
+            HeaderValue::from_str(etag).map_err(|e| ClientError::UnknownError(e.to_string()))?,
+        );
+        Ok(headers)
+    }
+}
+
+/// Conditional is the result of a conditional GET.
+#[derive(Debug, Clone)]
+pub enum Conditional<T> {
+    Modified(T),
+    NotModified,
+}
+
+/// Response headers which may carry the identifier Google assigned to a request, in order of
+/// preference. Quote it when contacting Google support.
+const REQUEST_ID_HEADERS: &[&str] = &["x-goog-request-id", "x-google-request-id", "x-request-id"];
+
+/// ApiResponse is a typed result along with the metadata of the response it came from, for
+/// auditing and support. elapsed covers the whole call, including any retries.
+#[derive(Debug, Clone)]
+pub struct ApiResponse<T> {
+    pub value: T,
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub request_id: Option<String>,
+    pub elapsed: Duration,
+}
+
+impl<T> ApiResponse<T> {
+    /// The ETag header of the response, if any.
+    pub fn etag(&self) -> Option<&str> {
+        self.headers.get(ETAG).and_then(|h| h.to_str().ok())
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Replace the value, keeping the metadata.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> ApiResponse<U> {
+        ApiResponse {
+            value: f(self.value),
+            status: self.status,
+            headers: self.headers,
+            request_id: self.request_id,
+            elapsed: self.elapsed,
+        }
+    }
+
+    /// Replace the value with the result of a fallible function, keeping the metadata.
+    pub fn try_map<U>(
+        self,
+        f: impl FnOnce(T) -> Result<U, ClientError>,
+    ) -> Result<ApiResponse<U>, ClientError> {
+        Ok(ApiResponse {
+            value: f(self.value)?,
+            status: self.status,
+            headers: self.headers,
+            request_id: self.request_id,
+            elapsed: self.elapsed,
+        })
+    }
+}
+
+impl ApiResponse<HttpResponse> {
+    fn new(resp: HttpResponse, elapsed: Duration) -> Self {
+        let request_id = REQUEST_ID_HEADERS.iter().find_map(|name| {
+            resp.headers()
+                .get(*name)
+                .and_then(|h| h.to_str().ok())
+                .map(|h| h.to_string())
+        });
+
+        Self {
+            status: resp.status(),
+            headers: resp.headers().clone(),
+            request_id,
+            elapsed,
+            value: resp,
+        }
+    }
+
+    /// Deserialize the response body, keeping the metadata.
+    pub fn json<T: serde::de::DeserializeOwned>(self) -> Result<ApiResponse<T>, ClientError> {
+        self.try_map(|resp| resp.json())
+    }
+}
+
+/// Define an API method along with its `_with_meta` variant from the body of the latter, which
+/// returns an ApiResponse. The variant is given a span named by `span` under the `tracing`
+/// feature, and the method returns only the value. Arguments cannot be declared `mut`; rebind
+/// them in the body instead.
+macro_rules! with_meta {
+    (
+        $(#[doc = $doc:literal])*
+        #[span = $span:literal]
+        $vis:vis async fn $name:ident(&$this:ident $(, $arg:ident: $ty:ty)* $(,)?) -> $ret:ty
+            as $meta:ident $body:block
+    ) => {
+        $(#[doc = $doc])*
+        $vis async fn $name(
+            &$this,
+            $($arg: $ty),*
+        ) -> Result<$ret, $crate::client::ClientError> {
+            $this
+                .$meta($($arg),*)
+                .await
+                .map($crate::client::ApiResponse::into_inner)
+        }
+
+        #[doc = concat!(
+            "As with ",
+            stringify!($name),
+            ", also returning the metadata of the response."
+        )]
+        #[cfg_attr(feature = "tracing", tracing::instrument(name = $span, skip_all))]
+        $vis async fn $meta(
+            &$this,
+            $($arg: $ty),*
+        ) -> Result<$crate::client::ApiResponse<$ret>, $crate::client::ClientError> $body
+    };
+}
+
+pub(crate) use with_meta;
+
 /// Client is a Google Calendar client. The access key must have already been fetched and the oauth
 /// negotiation should have already been completed. The client itself only implements HTTP verbs
 /// that accept Sendable implementations. You must use the decorated clients such as EventClient
 /// and CalendarListClient to do transactional work.
 #[derive(Debug, Clone)]
 pub struct Client {
-    client: reqwest::Client,
-    access_key: String,
+    transport: Arc<dyn Transport>,
+    token: Arc<dyn TokenProvider>,
+    base_url: String,
     headers: Option<HeaderMap<HeaderValue>>,
-    debug: bool,
+    quota_user: Option<String>,
+    retry: RetryPolicy,
+    governor: Option<QuotaGovernor>,
+    concurrency: Option<ConcurrencyLimit>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    cache: Option<ResponseCache>,
+    dry_run: Option<DryRun>,
+    lease: Option<Lease>,
 }
 
 impl Client {
     /// Create a new client. Requires an access key.
     pub fn new(access_key: String) -> Result<Self, ClientError> {
-        let client = ClientBuilder::new().gzip(true).https_only(true).build()?;
+        Self::with_token_provider(StaticToken::new(access_key))
+    }
+
+    /// Create a new client which obtains its access tokens from the provider, e.g. a
+    /// RefreshingToken which renews expired tokens on its own.
+    pub fn with_token_provider(
+        provider: impl TokenProvider + 'static,
+    ) -> Result<Self, ClientError> {
+        Ok(Self::from_parts(
+            Arc::new(provider),
+            Arc::new(ReqwestTransport::new(true)?),
+            Vec::new(),
+        ))
+    }
 
-        Ok(Self {
-            client,
-            access_key,
+    pub(crate) fn from_parts(
+        token: Arc<dyn TokenProvider>,
+        transport: Arc<dyn Transport>,
+        middleware: Vec<Arc<dyn Middleware>>,
+    ) -> Self {
+        Self {
+            transport,
+            token,
+            base_url: BASE_URL.to_string(),
             headers: None,
-            debug: false,
-        })
+            quota_user: None,
+            retry: RetryPolicy::none(),
+            governor: None,
+            concurrency: None,
+            middleware,
+            cache: None,
+            dry_run: None,
+            lease: None,
+        }
     }
 
+    /// Log requests and responses to stderr, with access tokens redacted. This is a shorthand
+    /// for adding a LoggingMiddleware.
     pub fn set_debug(&mut self) {
-        self.debug = true
+        self.add_middleware(LoggingMiddleware::new())
+    }
+
+    /// Add a middleware which observes and may modify each request and response.
+    pub fn add_middleware(&mut self, middleware: impl Middleware + 'static) {
+        self.middleware.push(Arc::new(middleware))
+    }
+
+    /// Point the client at a different API root, such as a local stand-in for the Calendar v3
+    /// API. The default is BASE_URL. Plain HTTP roots additionally require allow_plain_http.
+    pub fn set_base_url(&mut self, base_url: &str) -> Result<(), ClientError> {
+        url::Url::parse(base_url)?;
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        Ok(())
+    }
+
+    /// The API root requests are currently sent to.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Permit requests over plain HTTP. Only intended for talking to local test servers; the
+    /// client, and token requests made by the OAuth and service account flows, are HTTPS-only
+    /// by default so that access tokens are never sent in the clear. This replaces the transport
+    /// with a ReqwestTransport.
+    pub fn allow_plain_http(&mut self) -> Result<(), ClientError> {
+        self.transport = Arc::new(ReqwestTransport::new(false)?);
+        Ok(())
     }
 
-    fn set_bearer(&self, req: RequestBuilder) -> RequestBuilder {
-        req.header("Authorization", format!("Bearer {}", self.access_key))
+    /// Send the headers with every request. Headers set for an individual request take
+    /// precedence.
+    pub fn set_default_headers(&mut self, headers: HeaderMap) {
+        self.headers = (!headers.is_empty()).then_some(headers)
     }
 
-    async fn send(&self, mut req: RequestBuilder) -> Result<Response, ClientError> {
-        if let Some(headers) = &self.headers {
-            req = req.headers(headers.clone())
+    /// Attribute requests to this user for quota purposes via the quotaUser query parameter, or
+    /// stop doing so with None.
+    pub fn set_quota_user(&mut self, quota_user: Option<String>) {
+        self.quota_user = quota_user
+    }
+
+    /// Send requests through the transport instead of the default ReqwestTransport.
+    pub fn set_transport(&mut self, transport: impl Transport + 'static) {
+        self.transport = Arc::new(transport)
+    }
+
+    /// Retry rate-limited and transiently failing requests according to the policy. Client::new
+    /// does not retry.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry = policy
+    }
+
+    /// Throttle requests with the governor before they are sent. Clients cloned from this one
+    /// share its budget.
+    pub fn set_quota_governor(&mut self, governor: QuotaGovernor) {
+        self.governor = Some(governor)
+    }
+
+    /// Wait for the limit before sending each request, so that no more than its maximum are in
+    /// flight at once. Clients cloned from this one share the limit.
+    pub fn set_concurrency_limit(&mut self, limit: ConcurrencyLimit) {
+        self.concurrency = Some(limit)
+    }
+
+    /// Record interactions to, or replay them from, the cassette. Replaying clients never touch
+    /// the network, so the access token may be a placeholder. This wraps the current transport,
+    /// so set the cassette after set_transport or allow_plain_http.
+    pub fn set_cassette(&mut self, cassette: Cassette) {
+        self.transport = Arc::new(CassetteTransport::new(cassette, self.transport.clone()))
+    }
+
+    /// Capture mutations into the plan instead of sending them; GET requests are still sent.
+    /// Changing the transport afterwards does not end the dry run.
+    pub fn set_dry_run(&mut self, dry_run: DryRun) {
+        self.dry_run = Some(dry_run)
+    }
+
+    /// Cache GET responses which carry an ETag, revalidating them on each request. Clients
+    /// cloned from this one share the cache.
+    pub fn set_response_cache(&mut self, cache: ResponseCache) {
+        self.cache = Some(cache)
+    }
+
+    /// Count this client and its clones as held outside the ClientPool it came from.
+    pub(crate) fn set_lease(&mut self, lease: Lease) {
+        self.lease = Some(lease)
+    }
+
+    pub(crate) async fn execute(
+        &self,
+        method: Method,
+        mut url: url::Url,
+        extra_headers: HeaderMap,
+        body: Option<Vec<u8>>,
+    ) -> Result<HttpResponse, ClientError> {
+        if let Some(quota_user) = &self.quota_user {
+            url.query_pairs_mut().append_pair("quotaUser", quota_user);
+        }
+
+        #[cfg(feature = "tracing")]
+        {
+            use tracing::Instrument;
+
+            let span = crate::telemetry::request_span(&method, &url);
+            let started = Instant::now();
+            let res = self
+                .send_cached(method, url, extra_headers, body)
+                .instrument(span.clone())
+                .await;
+            crate::telemetry::record_result(&span, &res, started.elapsed());
+            res
+        }
+
+        #[cfg(not(feature = "tracing"))]
+        self.send_cached(method, url, extra_headers, body).await
+    }
+
+    async fn send_cached(
+        &self,
+        method: Method,
+        url: url::Url,
+        mut extra_headers: HeaderMap,
+        body: Option<Vec<u8>>,
+    ) -> Result<HttpResponse, ClientError> {
+        // callers sending their own If-None-Match want to see the 304 themselves.
+        let cache = match &self.cache {
+            Some(cache) if method == Method::GET && !extra_headers.contains_key(IF_NONE_MATCH) => {
+                cache
+            }
+            _ => return self.send_attempts(method, url, extra_headers, body).await,
+        };
+
+        let key = cache.key(self.token.as_ref(), &url).await?;
+        let cached = cache.store().get(&key).await;
+        if let Some(cached) = &cached {
+            extra_headers.insert(
+                IF_NONE_MATCH,
+                HeaderValue::from_str(&cached.etag)
+                    .map_err(|e| ClientError::UnknownError(e.to_string()))?,
+            );
+        }
+
+        let resp = self.send_attempts(method, url, extra_headers, body).await?;
+
+        if resp.status() == StatusCode::NOT_MODIFIED {
+            if let Some(cached) = cached {
+                return Ok(cached.to_response());
+            }
+        } else if resp.status().is_success() {
+            match CachedResponse::from_response(&resp) {
+                Some(entry) => cache.store().put(&key, entry).await,
+                None => cache.store().remove(&key).await,
+            }
         }
 
-        let resp = self.set_bearer(req).send().await?;
-        if resp.status() != 200 {
-            if let Some(header) = resp.headers().get("WWW-Authenticate") {
-                if header
-                    .to_str()?
-                    .starts_with(r#"Bearer error="invalid_token""#)
+        Ok(resp)
+    }
+
+    async fn send_attempts(
+        &self,
+        method: Method,
+        url: url::Url,
+        extra_headers: HeaderMap,
+        body: Option<Vec<u8>>,
+    ) -> Result<HttpResponse, ClientError> {
+        let mut token = self.token.token().await?;
+        let mut refreshed = false;
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+
+            #[cfg(feature = "tracing")]
+            crate::telemetry::record_retries(attempt);
+
+            if let Some(governor) = &self.governor {
+                governor.acquire().await;
+            }
+
+            // the request is rebuilt on each attempt so the body can be replayed.
+            let mut headers = self.headers.clone().unwrap_or_default();
+            headers.extend(extra_headers.clone());
+            headers.insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", token))
+                    .map_err(|e| ClientError::UnknownError(e.to_string()))?,
+            );
+
+            let mut req = HttpRequest {
+                method: method.clone(),
+                url: url.clone(),
+                headers,
+                body: body.clone(),
+            };
+
+            // the permit is taken first so that time spent queued is not counted as latency.
+            let permit = match &self.concurrency {
+                Some(limit) => Some(limit.acquire().await),
+                None => None,
+            };
+
+            let ctx = RequestContext {
+                method: method.clone(),
+                url: url.clone(),
+                attempt,
+                started: Instant::now(),
+            };
+
+            for middleware in &self.middleware {
+                middleware.on_request(&ctx, &mut req);
+            }
+
+            let result = match &self.dry_run {
+                Some(dry_run) => dry_run.execute(self.transport.as_ref(), req).await,
+                None => self.transport.execute(req).await,
+            };
+            drop(permit);
+
+            let resp = match result {
+                Ok(resp) => self
+                    .middleware
+                    .iter()
+                    .rev()
+                    .fold(resp, |resp, middleware| middleware.on_response(&ctx, resp)),
+                Err(e) => {
+                    for middleware in self.middleware.iter().rev() {
+                        middleware.on_error(&ctx, &e);
+                    }
+
+                    if retryable_transport_error(&e, &method) && self.retry.should_retry(attempt) {
+                        tokio::time::sleep(self.retry.backoff(attempt, None)).await;
+                        continue;
+                    }
+
+                    return Err(e.into());
+                }
+            };
+
+            let headers = resp.headers().clone();
+            match self.check_response(resp) {
+                Err(ClientError::InvalidToken) if !refreshed => {
+                    // a refresh does not count against the retry policy.
+                    token = self.token.refresh(&token).await?;
+                    refreshed = true;
+                    attempt -= 1;
+                }
+                Err(err)
+                    if retryable_error(&err)
+                        && self.retry.should_retry(attempt)
+                        && self.retry.accepts_retry_after(retry_after(&headers)) =>
                 {
-                    return Err(ClientError::InvalidToken);
+                    tokio::time::sleep(self.retry.backoff(attempt, retry_after(&headers))).await;
                 }
+                res => return res,
             }
+        }
+    }
 
-            Ok(resp.error_for_status()?)
+    fn check_response(&self, resp: HttpResponse) -> Result<HttpResponse, ClientError> {
+        let status = resp.status();
+        if status.is_client_error() || status.is_server_error() {
+            Err(ClientError::from_response(
+                status,
+                resp.headers(),
+                resp.text(),
+            ))
         } else {
             Ok(resp)
         }
     }
 
-    fn get_url(
+    pub(crate) fn get_url(
         &self,
-        method: &str,
         target: &impl Sendable,
         action: Option<String>,
     ) -> Result<url::Url, ClientError> {
-        let url = target.url(action)?;
-
-        if self.debug {
-            let byt = target.body_bytes()?;
-            eprintln!(
-                "[{}] {} | {}",
-                method,
-                url,
-                String::from_utf8(byt).unwrap_or_default()
-            );
-        }
+        target.url(&self.base_url, action)
+    }
+
+    /// Start a batch of requests which will be sent to the batch endpoint in a single call.
+    pub fn batch(&self) -> Batch {
+        Batch::new(self.clone())
+    }
 
-        Ok(url)
+    /// Perform a request with additional headers, such as the preconditions built by
+    /// Precondition::headers. The body is only sent for methods which carry one.
+    pub async fn request(
+        &self,
+        method: Method,
+        action: Option<String>,
+        target: impl Sendable,
+        headers: HeaderMap,
+    ) -> Result<HttpResponse, ClientError> {
+        let url = self.get_url(&target, action)?;
+        let body = if method == Method::GET || method == Method::DELETE {
+            None
+        } else {
+            Some(target.body_bytes()?)
+        };
+
+        self.execute(method, url, headers, body).await
+    }
+
+    /// Perform a request as with request, returning the response along with its metadata.
+    pub async fn request_with_meta(
+        &self,
+        method: Method,
+        action: Option<String>,
+        target: impl Sendable,
+        headers: HeaderMap,
+    ) -> Result<ApiResponse<HttpResponse>, ClientError> {
+        let started = Instant::now();
+        let resp = self.request(method, action, target, headers).await?;
+        Ok(ApiResponse::new(resp, started.elapsed()))
+    }
+
+    /// Send the target with its own method and action, returning the deserialized response.
+    pub async fn send<S: Sendable>(&self, target: S) -> Result<S::Response, ClientError> {
+        self.send_with_meta(target)
+            .await
+            .map(ApiResponse::into_inner)
+    }
+
+    /// Send the target as with send, returning the deserialized response along with its
+    /// metadata.
+    pub async fn send_with_meta<S: Sendable>(
+        &self,
+        target: S,
+    ) -> Result<ApiResponse<S::Response>, ClientError> {
+        let method = target.method();
+        let action = target.action();
+        self.request_with_meta(method, action, target, HeaderMap::new())
+            .await?
+            .json()
     }
 
     /// Perform a GET request.
@@ -132,8 +831,8 @@ impl Client {
         &self,
         action: Option<String>,
         target: impl Sendable,
-    ) -> Result<Response, ClientError> {
-        self.send(self.client.get(self.get_url("GET", &target, action)?))
+    ) -> Result<HttpResponse, ClientError> {
+        self.request(Method::GET, action, target, HeaderMap::new())
             .await
     }
 
@@ -142,13 +841,9 @@ impl Client {
         &self,
         action: Option<String>,
         target: impl Sendable,
-    ) -> Result<Response, ClientError> {
-        self.send(
-            self.client
-                .post(self.get_url("POST", &target, action)?)
-                .body(target.body_bytes()?),
-        )
-        .await
+    ) -> Result<HttpResponse, ClientError> {
+        self.request(Method::POST, action, target, HeaderMap::new())
+            .await
     }
 
     /// Perform a PUT request.
@@ -156,13 +851,9 @@ impl Client {
         &self,
         action: Option<String>,
         target: impl Sendable,
-    ) -> Result<Response, ClientError> {
-        self.send(
-            self.client
-                .put(self.get_url("PUT", &target, action)?)
-                .body(target.body_bytes()?),
-        )
-        .await
+    ) -> Result<HttpResponse, ClientError> {
+        self.request(Method::PUT, action, target, HeaderMap::new())
+            .await
     }
 
     /// Perform a PATCH request.
@@ -170,13 +861,9 @@ impl Client {
         &self,
         action: Option<String>,
         target: impl Sendable,
-    ) -> Result<Response, ClientError> {
-        self.send(
-            self.client
-                .patch(self.get_url("PATCH", &target, action)?)
-                .body(target.body_bytes()?),
-        )
-        .await
+    ) -> Result<HttpResponse, ClientError> {
+        self.request(Method::PATCH, action, target, HeaderMap::new())
+            .await
     }
 
     /// Perform a DELETE request.
@@ -184,8 +871,171 @@ impl Client {
         &self,
         action: Option<String>,
         target: impl Sendable,
-    ) -> Result<Response, ClientError> {
-        self.send(self.client.delete(self.get_url("DELETE", &target, action)?))
+    ) -> Result<HttpResponse, ClientError> {
+        self.request(Method::DELETE, action, target, HeaderMap::new())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::fake::FakeTransport;
+
+    fn error_body(code: u16, reason: &str) -> String {
+        serde_json::json!({
+            "error": {
+                "code": code,
+                "message": "something went wrong",
+                "errors": [{ "domain": "usageLimits", "reason": reason, "message": "" }],
+            }
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn classifies_by_status() {
+        let headers = HeaderMap::new();
+        let classify = |status| ClientError::from_response(status, &headers, "{}".to_string());
+
+        assert!(matches!(
+            classify(StatusCode::BAD_REQUEST),
+            ClientError::BadRequest(_)
+        ));
+        assert!(matches!(
+            classify(StatusCode::NOT_FOUND),
+            ClientError::NotFound(_)
+        ));
+        assert!(matches!(
+            classify(StatusCode::CONFLICT),
+            ClientError::Conflict(_)
+        ));
+        assert!(matches!(classify(StatusCode::GONE), ClientError::Gone(_)));
+        assert!(matches!(
+            classify(StatusCode::PRECONDITION_FAILED),
+            ClientError::PreconditionFailed(_)
+        ));
+        assert!(matches!(
+            classify(StatusCode::TOO_MANY_REQUESTS),
+            ClientError::RateLimited(_)
+        ));
+        assert!(matches!(
+            classify(StatusCode::INTERNAL_SERVER_ERROR),
+            ClientError::ApiError(_)
+        ));
+    }
+
+    #[test]
+    fn classifies_forbidden_by_reason() {
+        let headers = HeaderMap::new();
+        let classify = |reason| {
+            ClientError::from_response(StatusCode::FORBIDDEN, &headers, error_body(403, reason))
+        };
+
+        assert!(matches!(
+            classify("rateLimitExceeded"),
+            ClientError::RateLimited(_)
+        ));
+        assert!(matches!(
+            classify("userRateLimitExceeded"),
+            ClientError::RateLimited(_)
+        ));
+        assert!(matches!(
+            classify("quotaExceeded"),
+            ClientError::QuotaExceeded(_)
+        ));
+        assert!(matches!(
+            classify("dailyLimitExceeded"),
+            ClientError::QuotaExceeded(_)
+        ));
+        assert!(matches!(classify("forbidden"), ClientError::Forbidden(_)));
+    }
+
+    const GOOGLE_CHALLENGE: &str =
+        r#"Bearer realm="https://accounts.google.com/", error="invalid_token""#;
+
+    #[test]
+    fn classifies_invalid_token() {
+        let mut headers = HeaderMap::new();
+        headers.insert(WWW_AUTHENTICATE, HeaderValue::from_static(GOOGLE_CHALLENGE));
+
+        assert!(matches!(
+            ClientError::from_response(StatusCode::UNAUTHORIZED, &headers, String::new()),
+            ClientError::InvalidToken
+        ));
+        assert!(matches!(
+            ClientError::from_response(StatusCode::UNAUTHORIZED, &HeaderMap::new(), String::new()),
+            ClientError::InvalidToken
+        ));
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct CountingToken(Arc<std::sync::Mutex<Vec<String>>>);
+
+    #[async_trait::async_trait]
+    impl TokenProvider for CountingToken {
+        async fn token(&self) -> Result<String, ClientError> {
+            Ok("stale".to_string())
+        }
+
+        async fn refresh(&self, rejected: &str) -> Result<String, ClientError> {
+            self.0.lock().unwrap().push(rejected.to_string());
+            Ok("fresh".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn refreshes_rejected_token_and_retries() {
+        let provider = CountingToken::default();
+        let transport = FakeTransport::new();
+        let mut headers = HeaderMap::new();
+        headers.insert(WWW_AUTHENTICATE, HeaderValue::from_static(GOOGLE_CHALLENGE));
+        transport.respond(StatusCode::UNAUTHORIZED, headers, "");
+        transport.respond_json(StatusCode::OK, r#"{"items":[]}"#);
+
+        let mut client = Client::with_token_provider(provider.clone()).unwrap();
+        client.set_transport(transport.clone());
+        let resp = client
+            .get(None, crate::resources::CalendarList::default())
             .await
+            .unwrap();
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(*provider.0.lock().unwrap(), vec!["stale".to_string()]);
+
+        let auth = transport
+            .requests()
+            .iter()
+            .map(|req| req.headers[AUTHORIZATION].to_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(auth, vec!["Bearer stale", "Bearer fresh"]);
+    }
+
+    #[test]
+    fn parses_error_payload() {
+        let err = ClientError::from_response(
+            StatusCode::NOT_FOUND,
+            &HeaderMap::new(),
+            error_body(404, "notFound"),
+        );
+
+        let api = err.api_error().unwrap();
+        assert_eq!(api.status, StatusCode::NOT_FOUND);
+        assert_eq!(api.message, "something went wrong");
+        assert_eq!(api.reasons().collect::<Vec<_>>(), vec!["notFound"]);
+        assert_eq!(err.status(), Some(StatusCode::NOT_FOUND));
+    }
+
+    #[test]
+    fn keeps_unparseable_body() {
+        let err = ClientError::from_response(
+            StatusCode::BAD_GATEWAY,
+            &HeaderMap::new(),
+            "<html>bad gateway</html>".to_string(),
+        );
+
+        let api = err.api_error().unwrap();
+        assert_eq!(api.message, "<html>bad gateway</html>");
+        assert!(api.errors.is_empty());
     }
 }
@@ -0,0 +1,279 @@
+use crate::{
+    batch::{decode_requests, encode_responses, response_boundary},
+    transport::{HttpRequest, HttpResponse, Transport, TransportError, TransportErrorKind},
+};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE},
+    Method, StatusCode,
+};
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+/// A mutation which a dry-run Client would have sent.
+#[derive(Debug, Clone)]
+pub struct PlannedRequest {
+    pub method: Method,
+    pub url: Url,
+    pub body: Option<String>,
+}
+
+impl PlannedRequest {
+    /// Deserialize the body which would have been sent.
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Option<T> {
+        serde_json::from_str(self.body.as_deref()?).ok()
+    }
+}
+
+impl std::fmt::Display for PlannedRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.method, self.url.path())?;
+        if let Some(query) = self.url.query().filter(|q| !q.is_empty()) {
+            write!(f, "?{}", query)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// DryRun collects the plan of a dry-run Client. GET requests are still sent, but POST, PUT,
+/// PATCH and DELETE requests, including those inside batches, are added to the plan instead and
+/// answered with a synthesized response: DELETE with 204 No Content, everything else with 200 OK
+/// echoing the request body. Newly created resources are given a random ID. Install it with
+/// Client::set_dry_run; clones share the plan.
+#[derive(Debug, Clone, Default)]
+pub struct DryRun {
+    plan: Arc<Mutex<Vec<PlannedRequest>>>,
+}
+
+impl DryRun {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The mutations captured so far, in the order they were made.
+    pub fn plan(&self) -> Vec<PlannedRequest> {
+        self.plan.lock().unwrap().clone()
+    }
+
+    /// Return the captured mutations, leaving the plan empty.
+    pub fn take(&self) -> Vec<PlannedRequest> {
+        std::mem::take(&mut *self.plan.lock().unwrap())
+    }
+
+    fn push(&self, method: Method, url: Url, body: Option<&[u8]>) {
+        self.plan.lock().unwrap().push(PlannedRequest {
+            method,
+            url,
+            body: body.map(|b| String::from_utf8_lossy(b).to_string()),
+        })
+    }
+}
+
+/// Answer a mutation as the API plausibly would have.
+fn synthesize(method: &Method, body: Option<&[u8]>) -> HttpResponse {
+    if method == Method::DELETE {
+        return HttpResponse {
+            status: StatusCode::NO_CONTENT,
+            headers: HeaderMap::new(),
+            body: Vec::new(),
+        };
+    }
+
+    let mut value = body
+        .and_then(|b| serde_json::from_slice::<serde_json::Value>(b).ok())
+        .filter(|v| v.is_object())
+        .unwrap_or_else(|| serde_json::json!({}));
+
+    if method == Method::POST {
+        if let Some(object) = value.as_object_mut() {
+            // a simple UUID is a valid event ID: lowercase base32hex.
+            object
+                .entry("id")
+                .or_insert_with(|| uuid::Uuid::new_v4().simple().to_string().into());
+        }
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+    HttpResponse {
+        status: StatusCode::OK,
+        headers,
+        body: value.to_string().into_bytes(),
+    }
+}
+
+impl DryRun {
+    /// Send a request through the transport if it is a GET, or add it to the plan otherwise.
+    /// The Client calls this in place of the transport while a dry run is installed.
+    pub(crate) async fn execute(
+        &self,
+        transport: &dyn Transport,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, TransportError> {
+        if req.method == Method::GET {
+            return transport.execute(req).await;
+        }
+
+        let boundary = req
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|h| h.to_str().ok())
+            .filter(|ct| ct.starts_with("multipart/mixed"))
+            .and_then(response_boundary);
+
+        if let Some(boundary) = boundary {
+            return self.batch(transport, req, boundary).await;
+        }
+
+        self.push(req.method.clone(), req.url.clone(), req.body.as_deref());
+        Ok(synthesize(&req.method, req.body.as_deref()))
+    }
+
+    /// Answer each operation of a batch separately, sending the GETs on their own.
+    async fn batch(
+        &self,
+        transport: &dyn Transport,
+        req: HttpRequest,
+        boundary: String,
+    ) -> Result<HttpResponse, TransportError> {
+        let body = String::from_utf8_lossy(req.body.as_deref().unwrap_or_default()).to_string();
+        let mut responses = Vec::new();
+
+        for part in decode_requests(&body, &boundary) {
+            let url = req
+                .url
+                .join(&part.target)
+                .map_err(|e| TransportError::new(TransportErrorKind::Other, e.to_string()))?;
+
+            if part.method == Method::GET {
+                let mut headers = req.headers.clone();
+                headers.remove(CONTENT_TYPE);
+                headers.remove(CONTENT_LENGTH);
+
+                responses.push(
+                    transport
+                        .execute(HttpRequest {
+                            method: Method::GET,
+                            url,
+                            headers,
+                            body: None,
+                        })
+                        .await?,
+                );
+            } else {
+                self.push(part.method.clone(), url, part.body.as_deref());
+                responses.push(synthesize(&part.method, part.body.as_deref()));
+            }
+        }
+
+        let boundary = format!("batch_{}", uuid::Uuid::new_v4().simple());
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_str(&format!("multipart/mixed; boundary={}", boundary))
+                .map_err(|e| TransportError::new(TransportErrorKind::Other, e.to_string()))?,
+        );
+
+        Ok(HttpResponse {
+            status: StatusCode::OK,
+            headers,
+            body: encode_responses(&responses, &boundary),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::Client, resources::Event, transport::fake::FakeTransport};
+
+    fn event(id: Option<&str>) -> Event {
+        let mut event = Event::default();
+        event.calendar_id = Some("primary".to_string());
+        event.id = id.map(ToString::to_string);
+        event.summary = Some("standup".to_string());
+        event
+    }
+
+    #[tokio::test]
+    async fn mutations_are_planned() {
+        let dry_run = DryRun::new();
+        let mut client = Client::new("token".to_string()).unwrap();
+        client.set_dry_run(dry_run.clone());
+
+        // replacing the transport must not end the dry run.
+        let transport = FakeTransport::new();
+        client.set_transport(transport.clone());
+
+        let resp = client.post(None, event(None)).await.unwrap();
+        let created = resp.json::<Event>().unwrap();
+        assert!(created.id.is_some());
+        assert_eq!(created.summary.as_deref(), Some("standup"));
+
+        let resp = client.delete(None, event(Some("e1"))).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        client.get(None, event(Some("e1"))).await.unwrap();
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, Method::GET);
+
+        let plan = dry_run.plan();
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan[0].method, Method::POST);
+        assert_eq!(
+            plan[0].json::<Event>().unwrap().summary.as_deref(),
+            Some("standup")
+        );
+        assert_eq!(
+            plan[1].to_string(),
+            "DELETE /calendar/v3/calendars/primary/events/e1"
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_mutations_are_planned() {
+        let dry_run = DryRun::new();
+        let transport = FakeTransport::new();
+        transport.respond_json(StatusCode::OK, r#"{"id":"e1"}"#);
+
+        let mut client = Client::new("token".to_string()).unwrap();
+        client.set_transport(transport.clone());
+        client.set_dry_run(dry_run.clone());
+
+        let mut batch = client.batch();
+        batch.get(None, event(Some("e1"))).unwrap();
+        batch.patch(None, event(Some("e1"))).unwrap();
+        batch.delete(None, event(Some("e2"))).unwrap();
+        let results = batch.send().await.unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[0]
+                .as_ref()
+                .unwrap()
+                .json::<Event>()
+                .unwrap()
+                .id
+                .as_deref(),
+            Some("e1")
+        );
+        assert_eq!(results[2].as_ref().unwrap().status, StatusCode::NO_CONTENT);
+
+        // only the GET reaches the transport, on its own rather than as a batch.
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, Method::GET);
+        assert!(requests[0]
+            .url
+            .path()
+            .ends_with("/calendars/primary/events/e1"));
+
+        let plan = dry_run.plan();
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan[0].method, Method::PATCH);
+        assert_eq!(plan[1].method, Method::DELETE);
+    }
+}
@@ -21,14 +21,62 @@
 //! }
 //! ```
 
+/// Batch requests, sending many operations in a single call.
+pub mod batch;
+/// Synchronous clients for the `blocking` feature.
+#[cfg(feature = "blocking")]
+pub mod blocking;
+/// ClientBuilder, for configuring timeouts, proxies and default headers.
+pub mod builder;
+/// Bounded-concurrency execution of many event operations, with resumable checkpoints.
+pub mod bulk;
+/// Caching of GET responses, revalidated by ETag.
+pub mod cache;
+/// Record and replay of HTTP interactions for offline testing.
+pub mod cassette;
 /// Core client, used to construct other clients.
 pub mod client;
+/// Dry-run mode, which plans mutations instead of sending them.
+pub mod dryrun;
+/// Middleware for observing and modifying requests and responses.
+pub mod middleware;
 pub mod oauth;
+/// Helpers for paginated listings.
+pub mod pagination;
+/// Pools of clients acting on behalf of many users.
+pub mod pool;
+/// Client-side rate limiting to stay within Google's quotas.
+pub mod ratelimit;
 /// Resource clients and structures.
 pub mod resources;
+/// Retry policies for rate-limited and transiently failing requests.
+pub mod retry;
 /// Sendable trait for constructing your own queries to Google Calendar through the client.
 pub mod sendable;
+/// Service account authentication with the JWT bearer grant.
+pub mod service_account;
+/// Span helpers for the `tracing` feature.
+#[cfg(feature = "tracing")]
+mod telemetry;
+/// Access token providers, including one which refreshes expired tokens automatically.
+pub mod token;
+/// Pluggable HTTP transports.
+pub mod transport;
 
+pub use batch::*;
+pub use builder::*;
+pub use bulk::*;
+pub use cache::*;
+pub use cassette::*;
 pub use client::*;
+pub use dryrun::*;
+pub use middleware::*;
 pub use oauth::*;
+pub use pagination::collect_all;
+pub use pool::*;
+pub use ratelimit::*;
 pub use resources::*;
+pub use retry::*;
+pub use service_account::*;
+pub use token::*;
+pub use transport::*;
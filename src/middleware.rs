@@ -0,0 +1,328 @@
+use crate::transport::{HttpRequest, HttpResponse, TransportError};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, AUTHORIZATION},
+    Method, StatusCode,
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use url::Url;
+
+/// Query parameters which carry credentials and are hidden by redact_url.
+const SECRET_PARAMS: &[&str] = &["access_token", "key", "client_secret"];
+
+/// JSON fields which carry credentials and are hidden by redact_body.
+const SECRET_FIELDS: &[&str] = &[
+    "access_token",
+    "refresh_token",
+    "id_token",
+    "client_secret",
+    "assertion",
+    "token",
+    "password",
+    "passcode",
+    "pin",
+];
+
+/// RequestContext describes the request a middleware is being called for. It is the same for
+/// on_request and the matching on_response or on_error call.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub method: Method,
+    pub url: Url,
+    /// The 1-based attempt number; retries of the same call share a context otherwise.
+    pub attempt: u32,
+    pub started: Instant,
+}
+
+/// Middleware observes, and may modify, every request the Client sends and every response it
+/// receives, including retries. Middleware is run in the order it was added for requests and in
+/// reverse order for responses. Install it with Client::add_middleware.
+pub trait Middleware: std::fmt::Debug + Send + Sync {
+    /// Called with the fully built request, including the Authorization header, just before it
+    /// is sent.
+    fn on_request(&self, _ctx: &RequestContext, _req: &mut HttpRequest) {}
+
+    /// Called with the response before it is inspected for errors. The returned response is the
+    /// one the Client continues with.
+    fn on_response(&self, _ctx: &RequestContext, resp: HttpResponse) -> HttpResponse {
+        resp
+    }
+
+    /// Called when the transport could not send the request or receive a response.
+    fn on_error(&self, _ctx: &RequestContext, _err: &TransportError) {}
+}
+
+/// Render the URL with credential-bearing query parameters replaced by `<redacted>`.
+pub fn redact_url(url: &Url) -> String {
+    let mut url = url.clone();
+
+    if url.query().is_some() {
+        let pairs = url
+            .query_pairs()
+            .map(|(k, v)| {
+                let v = if SECRET_PARAMS.contains(&k.as_ref()) {
+                    "<redacted>".to_string()
+                } else {
+                    v.to_string()
+                };
+                (k.to_string(), v)
+            })
+            .collect::<Vec<_>>();
+
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+
+    url.to_string()
+}
+
+fn redact_value(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(object) => {
+            for (name, value) in object.iter_mut() {
+                if SECRET_FIELDS.contains(&name.as_str()) {
+                    *value = serde_json::Value::String("<redacted>".to_string());
+                } else {
+                    redact_value(value);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact_value),
+        _ => {}
+    }
+}
+
+/// Render a body with credential-bearing JSON fields, at any depth, replaced by `<redacted>`.
+/// Bodies which are not JSON are rendered as they are.
+pub fn redact_body(body: &[u8]) -> String {
+    match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(mut value) => {
+            redact_value(&mut value);
+            value.to_string()
+        }
+        Err(_) => String::from_utf8_lossy(body).to_string(),
+    }
+}
+
+/// Render the headers with the Authorization header replaced by `<redacted>`.
+pub fn redact_headers(headers: &HeaderMap) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            if name == AUTHORIZATION {
+                format!("{}: <redacted>", name)
+            } else {
+                format!("{}: {}", name, value.to_str().unwrap_or("<binary>"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+type Sink = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// LoggingMiddleware logs each request, with its body, and each response status, optionally with
+/// its body. Access tokens and other credentials are redacted. Logs go to stderr unless another
+/// sink is provided.
+#[derive(Clone)]
+pub struct LoggingMiddleware {
+    sink: Sink,
+    bodies: bool,
+    response_bodies: bool,
+}
+
+impl std::fmt::Debug for LoggingMiddleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoggingMiddleware")
+            .field("bodies", &self.bodies)
+            .field("response_bodies", &self.response_bodies)
+            .finish()
+    }
+}
+
+impl Default for LoggingMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoggingMiddleware {
+    pub fn new() -> Self {
+        Self::with_sink(|line| eprintln!("{}", line))
+    }
+
+    /// Send log lines to the provided function instead of stderr.
+    pub fn with_sink(sink: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        Self {
+            sink: Arc::new(sink),
+            bodies: true,
+            response_bodies: false,
+        }
+    }
+
+    /// Whether to include request bodies, which may contain personal data. Defaults to true.
+    pub fn bodies(mut self, bodies: bool) -> Self {
+        self.bodies = bodies;
+        self
+    }
+
+    /// Whether to include response bodies, which usually contain personal data. Defaults to
+    /// false.
+    pub fn response_bodies(mut self, response_bodies: bool) -> Self {
+        self.response_bodies = response_bodies;
+        self
+    }
+}
+
+impl Middleware for LoggingMiddleware {
+    fn on_request(&self, ctx: &RequestContext, req: &mut HttpRequest) {
+        let mut line = format!(
+            "[{}] {} (attempt {}) {{{}}}",
+            req.method,
+            redact_url(&req.url),
+            ctx.attempt,
+            redact_headers(&req.headers)
+        );
+
+        if self.bodies {
+            if let Some(body) = &req.body {
+                line.push_str(" | ");
+                line.push_str(&redact_body(body));
+            }
+        }
+
+        (self.sink)(&line)
+    }
+
+    fn on_response(&self, ctx: &RequestContext, resp: HttpResponse) -> HttpResponse {
+        let mut line = format!(
+            "[{}] {} -> {} in {:?}",
+            ctx.method,
+            redact_url(&ctx.url),
+            resp.status(),
+            ctx.started.elapsed()
+        );
+
+        if self.response_bodies && !resp.bytes().is_empty() {
+            line.push_str(" | ");
+            line.push_str(&redact_body(resp.bytes()));
+        }
+
+        (self.sink)(&line);
+        resp
+    }
+
+    fn on_error(&self, ctx: &RequestContext, err: &TransportError) {
+        (self.sink)(&format!(
+            "[{}] {} -> error: {} in {:?}",
+            ctx.method,
+            redact_url(&ctx.url),
+            err,
+            ctx.started.elapsed()
+        ))
+    }
+}
+
+/// HeaderMiddleware adds the headers to every request, replacing any existing values.
+#[derive(Debug, Clone)]
+pub struct HeaderMiddleware(HeaderMap<HeaderValue>);
+
+impl HeaderMiddleware {
+    pub fn new(headers: HeaderMap<HeaderValue>) -> Self {
+        Self(headers)
+    }
+}
+
+impl Middleware for HeaderMiddleware {
+    fn on_request(&self, _ctx: &RequestContext, req: &mut HttpRequest) {
+        for (name, value) in &self.0 {
+            req.headers.insert(name, value.clone());
+        }
+    }
+}
+
+type TimingCallback = Arc<dyn Fn(&RequestContext, Option<StatusCode>, Duration) + Send + Sync>;
+
+/// TimingMiddleware reports the latency of every attempt to the callback, along with the response
+/// status, or None if no response was received.
+#[derive(Clone)]
+pub struct TimingMiddleware(TimingCallback);
+
+impl std::fmt::Debug for TimingMiddleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TimingMiddleware")
+    }
+}
+
+impl TimingMiddleware {
+    pub fn new(
+        callback: impl Fn(&RequestContext, Option<StatusCode>, Duration) + Send + Sync + 'static,
+    ) -> Self {
+        Self(Arc::new(callback))
+    }
+}
+
+impl Middleware for TimingMiddleware {
+    fn on_response(&self, ctx: &RequestContext, resp: HttpResponse) -> HttpResponse {
+        (self.0)(ctx, Some(resp.status()), ctx.started.elapsed());
+        resp
+    }
+
+    fn on_error(&self, ctx: &RequestContext, _err: &TransportError) {
+        (self.0)(ctx, None, ctx.started.elapsed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn redacts_nested_secrets() {
+        let body = br#"{"summary":"x","conferenceData":{"entryPoints":[{"pin":"1234","uri":"tel:1"}]},"access_token":"ya29"}"#;
+        let redacted = redact_body(body);
+
+        assert!(!redacted.contains("1234"));
+        assert!(!redacted.contains("ya29"));
+        assert!(redacted.contains(r#""pin":"<redacted>""#));
+        assert!(redacted.contains(r#""uri":"tel:1""#));
+    }
+
+    #[test]
+    fn leaves_non_json_bodies() {
+        assert_eq!(redact_body(b"not json"), "not json");
+    }
+
+    #[test]
+    fn response_bodies_are_opt_in() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let lines = lines.clone();
+            move |line: &str| lines.lock().unwrap().push(line.to_string())
+        };
+
+        let ctx = RequestContext {
+            method: Method::GET,
+            url: Url::parse("https://example.com/calendar/v3/calendars/primary").unwrap(),
+            attempt: 1,
+            started: Instant::now(),
+        };
+        let resp = || HttpResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: br#"{"id":"primary","token":"secret"}"#.to_vec(),
+        };
+
+        LoggingMiddleware::with_sink(sink.clone()).on_response(&ctx, resp());
+        LoggingMiddleware::with_sink(sink)
+            .response_bodies(true)
+            .on_response(&ctx, resp());
+
+        let lines = lines.lock().unwrap();
+        assert!(!lines[0].contains("primary\""));
+        assert!(lines[1].contains(r#""id":"primary""#));
+        assert!(!lines[1].contains("secret"));
+    }
+}
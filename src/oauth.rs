@@ -12,10 +12,10 @@
 //!     };
 //!    
 //!     let state = State::new(Mutex::new(params.clone()));
-//!     let host = oauth_listener(state.clone()).await?;
-//!     params.redirect_url = Some(format!("http://{}", host));
-//!    
-//!     let url = oauth_user_url(params.clone());
+//!     oauth_listener(state.clone()).await?;
+//!
+//!     // the listener records the redirect_url and oauth_state it expects.
+//!     let url = oauth_user_url(state.lock().await.clone())?;
 //!     println!("Click on this and login: {}", url);
 //!    
 //!     loop {
@@ -24,11 +24,16 @@
 //!             println!("Captured {:?}. Thanks!", lock.access_key.unwrap());
 //!             return Ok(());
 //!         }
+//!
+//!         if let Some(err) = &lock.oauth_error {
+//!             eprintln!("Ignored a redirect: {}", err);
+//!         }
 //!    
 //!         tokio::time::sleep(std::time::Duration::new(1, 0)).await;
 //!     }
 //! }
 //! ```
+use crate::client::ClientError;
 use davisjr::prelude::*;
 use reqwest::{header::HeaderMap, ClientBuilder};
 use serde_derive::{Deserialize, Serialize};
@@ -66,6 +71,51 @@ pub struct ClientParameters {
     pub expires_at: Option<chrono::NaiveDateTime>,
     pub refresh_token: Option<String>,
     pub refresh_token_expires_at: Option<chrono::NaiveDateTime>,
+    /// Overrides TOKEN_URL, e.g. to exchange tokens with a local stand-in server.
+    pub token_url: Option<String>,
+    /// Permit a plain HTTP token_url, as Client::allow_plain_http does for API requests.
+    pub allow_plain_http: bool,
+    /// Overrides USER_URL when producing the OAuth capture URL.
+    pub user_url: Option<String>,
+    /// The random state sent with the OAuth capture URL. oauth_listener sets it if it is not
+    /// already set, and only accepts redirects which return it.
+    pub oauth_state: Option<String>,
+    /// The most recent redirect the oauth_listener refused.
+    pub oauth_error: Option<OAuthError>,
+}
+
+/// OAuthError describes a redirect the oauth_listener refused instead of exchanging its code.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum OAuthError {
+    /// The state did not match the one sent with the capture URL, so the redirect may have been
+    /// forged by another site.
+    #[error("State Mismatch: received {received:?}")]
+    StateMismatch { received: Option<String> },
+    /// Google returned an error, such as access_denied, instead of a code.
+    #[error("Authorization Failed: {0}")]
+    Authorization(String),
+    #[error("Missing Code")]
+    MissingCode,
+}
+
+impl ClientParameters {
+    /// Record a freshly issued access token, and its refresh token if one was provided.
+    pub fn apply_token(&mut self, token: AccessToken) {
+        self.access_key = Some(token.access_token);
+        self.expires_at =
+            Some(chrono::Local::now().naive_utc() + chrono::Duration::seconds(token.expires_in));
+
+        if let Some(refresh_token) = token.refresh_token {
+            self.refresh_token = Some(refresh_token);
+            if let Some(expires_in) = token.refresh_token_expires_in {
+                self.refresh_token_expires_at =
+                    Some(chrono::Local::now().naive_utc() + chrono::Duration::seconds(expires_in));
+            } else {
+                self.refresh_token_expires_at =
+                    Some(chrono::Local::now().naive_utc() + chrono::Duration::seconds(3600));
+            }
+        }
+    }
 }
 
 async fn handler(
@@ -83,20 +133,18 @@ async fn handler(
                 .map(|n| n.split("=").collect::<Vec<&str>>())
                 .collect::<Vec<Vec<&str>>>()
         })
-        .unwrap_or(Vec::new());
+        .unwrap_or_default();
 
     let mut code: Option<String> = None;
     let mut oauth_state: Option<String> = None;
+    let mut error: Option<String> = None;
 
     for pair in pairs {
-        if pair[0] == "code" {
-            code = Some(pair[1].to_string());
-        } else if pair[0] == "state" {
-            oauth_state = Some(pair[1].to_string());
-        }
-
-        if code.is_some() && oauth_state.is_some() {
-            break;
+        match pair[..] {
+            ["code", value] => code = Some(value.to_string()),
+            ["state", value] => oauth_state = Some(value.to_string()),
+            ["error", value] => error = Some(value.to_string()),
+            _ => {}
         }
     }
 
@@ -104,22 +152,42 @@ async fn handler(
     let lock = lock.lock().await;
     let mut lock = lock.lock().await;
 
+    // the state is checked first; an error from a forged redirect is not worth reporting.
+    let refused = if lock.oauth_state.is_none() || oauth_state != lock.oauth_state {
+        Some(OAuthError::StateMismatch {
+            received: oauth_state.clone(),
+        })
+    } else if let Some(error) = error {
+        Some(OAuthError::Authorization(error))
+    } else if code.is_none() {
+        Some(OAuthError::MissingCode)
+    } else {
+        None
+    };
+
+    if let Some(err) = refused {
+        lock.oauth_error = Some(err);
+
+        // the page is fixed, so that nothing from the request or the expected state is shown.
+        return Ok((
+            req,
+            Some(
+                Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .header("Content-Type", "text/plain; charset=utf-8")
+                    .body(Body::from(
+                        "Login failed. Please close this browser tab and start again.",
+                    ))
+                    .unwrap(),
+            ),
+            state,
+        ));
+    }
+
     let token =
         request_access_token(lock.clone(), code.as_deref(), oauth_state.as_deref(), false).await?;
-    lock.access_key = Some(token.access_token);
-    lock.expires_at =
-        Some(chrono::Local::now().naive_utc() + chrono::Duration::seconds(token.expires_in));
-
-    if let Some(refresh_token) = token.refresh_token {
-        lock.refresh_token = Some(refresh_token);
-        if let Some(expires_in) = token.refresh_token_expires_in {
-            lock.refresh_token_expires_at =
-                Some(chrono::Local::now().naive_utc() + chrono::Duration::seconds(expires_in));
-        } else {
-            lock.refresh_token_expires_at =
-                Some(chrono::Local::now().naive_utc() + chrono::Duration::seconds(3600));
-        }
-    }
+    lock.apply_token(token);
+    lock.oauth_error = None;
 
     Ok((
         req,
@@ -170,13 +238,18 @@ pub async fn request_access_token(
         params.push(("refresh_token", &token));
     }
 
+    let token_url = client_params
+        .token_url
+        .clone()
+        .unwrap_or_else(|| TOKEN_URL.to_string());
+
     let client = ClientBuilder::new()
         .default_headers(headers)
-        .https_only(true)
+        .https_only(!client_params.allow_plain_http)
         .build()?;
 
     Ok(client
-        .post(TOKEN_URL)
+        .post(token_url)
         .form(&params)
         .basic_auth(&client_params.client_id, Some(&client_params.client_secret))
         .send()
@@ -185,25 +258,42 @@ pub async fn request_access_token(
         .await?)
 }
 
-/// Produce a OAuth capture URL.
-pub fn oauth_user_url(params: ClientParameters) -> String {
-    // using the uuid is taken from a sight read of google_calendar; I'm not
-    // sure it's necessary to use a uuid but I am lazy
-    let u = uuid::Uuid::new_v4();
-    format!(
+/// Produce a OAuth capture URL. The parameters should come from the State given to
+/// oauth_listener, which sets the redirect_url and the oauth_state it expects. Parameters
+/// without either are an error, since the listener would refuse the redirect.
+pub fn oauth_user_url(params: ClientParameters) -> Result<String, ClientError> {
+    let redirect_url = params.redirect_url.ok_or_else(|| {
+        ClientError::UnknownError("the parameters have no redirect_url".to_string())
+    })?;
+    let oauth_state = params.oauth_state.ok_or_else(|| {
+        ClientError::UnknownError("the parameters have no oauth_state".to_string())
+    })?;
+
+    Ok(format!(
         "{}?client_id={}&access_type=offline&response_type=code&redirect_uri={}&state={}&scope={}",
-        USER_URL,
+        params.user_url.as_deref().unwrap_or(USER_URL),
         params.client_id,
-        params.redirect_url.expect("Expected a redirect URL"),
-        u,
+        redirect_url,
+        oauth_state,
         CALENDAR_SCOPE,
-    )
+    ))
 }
 
 /// Create a local listener which is ready to become the redirect_url. Once the state has been
 /// captured, it will mutate the provided state with the access credentials. Returned is the
 /// address of the listener suitable for coercing to the redirect_url.
+///
+/// A random oauth_state is recorded in the state unless one is already set. Redirects which do
+/// not return it are refused with an error page, and recorded in oauth_error.
 pub async fn oauth_listener(state: State) -> Result<String, ServerError> {
+    Ok(serve_listener(state).await?.0)
+}
+
+/// Start the oauth_listener, returning its address and the task serving it, which stops the
+/// listener when aborted.
+pub(crate) async fn serve_listener(
+    state: State,
+) -> Result<(String, tokio::task::JoinHandle<()>), ServerError> {
     let mut app = App::with_state(state.clone());
 
     app.get("/", compose_handler!(handler))?;
@@ -211,13 +301,43 @@ pub async fn oauth_listener(state: State) -> Result<String, ServerError> {
     // find a free port. this is susceptible to timing races and if that happens I guess they'll
     // just have to start the program again.
     let lis = tokio::net::TcpListener::bind("localhost:0").await?;
-    let addr = lis.local_addr()?.clone();
+    let addr = lis.local_addr()?;
     drop(lis);
 
     let mut lock = state.lock().await;
-    lock.redirect_url = Some(format!("http://{}", addr.to_string()));
+    lock.redirect_url = Some(format!("http://{}", addr));
+    lock.oauth_state
+        .get_or_insert_with(|| uuid::Uuid::new_v4().to_string());
 
-    tokio::spawn(async move { app.serve(&addr.to_string()).await.unwrap() });
+    let server = tokio::spawn(async move { app.serve(&addr.to_string()).await.unwrap() });
 
-    Ok(addr.to_string())
+    Ok((addr.to_string(), server))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_url_without_state() {
+        assert!(oauth_user_url(ClientParameters {
+            client_id: "id".to_string(),
+            redirect_url: Some("http://localhost:1234".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn user_url_with_state() {
+        let url = oauth_user_url(ClientParameters {
+            client_id: "id".to_string(),
+            redirect_url: Some("http://localhost:1234".to_string()),
+            oauth_state: Some("expected".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert!(url.contains("&state=expected&"));
+    }
 }
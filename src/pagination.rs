@@ -0,0 +1,141 @@
+use crate::client::{ApiResponse, ClientError};
+use futures::{stream, Stream, TryStreamExt};
+use std::future::Future;
+
+/// Turn a page fetcher into a stream of items. The fetcher is called with the page token of the
+/// page to retrieve (None for the first page) and returns the items on it along with the
+/// nextPageToken, if any. The stream ends after the page without a nextPageToken, or with an
+/// empty one.
+pub(crate) fn paginate<T, F, Fut>(fetch: F) -> impl Stream<Item = Result<T, ClientError>>
+where
+    F: Fn(Option<String>) -> Fut,
+    Fut: Future<Output = Result<(Vec<T>, Option<String>), ClientError>>,
+{
+    stream::try_unfold(Some(None), move |page_token: Option<Option<String>>| {
+        let fut = page_token.map(&fetch);
+
+        async move {
+            match fut {
+                Some(fut) => {
+                    let (items, next) = fut.await?;
+                    // an empty token is sent by some endpoints on the last page.
+                    let next = next.filter(|token| !token.is_empty());
+                    Ok::<_, ClientError>(Some((items, next.map(Some))))
+                }
+                None => Ok(None),
+            }
+        }
+    })
+    .map_ok(|items| stream::iter(items.into_iter().map(Ok)))
+    .try_flatten()
+}
+
+/// Retrieve every page of a listing along with the metadata of the response it came from. fetch
+/// is called as with paginate and returns the whole page; next_page_token reads the
+/// nextPageToken from it.
+pub(crate) async fn collect_pages<P, F, Fut>(
+    fetch: F,
+    next_page_token: impl Fn(&P) -> Option<String>,
+) -> Result<Vec<ApiResponse<P>>, ClientError>
+where
+    F: Fn(Option<String>) -> Fut,
+    Fut: Future<Output = Result<ApiResponse<P>, ClientError>>,
+{
+    let next_page_token = &next_page_token;
+
+    collect_all(paginate(|page_token| {
+        let fut = fetch(page_token);
+
+        async move {
+            let page = fut.await?;
+            let next = next_page_token(&page.value);
+            Ok((vec![page], next))
+        }
+    }))
+    .await
+}
+
+/// Drain a paginated stream, such as EventClient::list_stream, into a Vec. Stops at the first
+/// error.
+pub async fn collect_all<T>(
+    stream: impl Stream<Item = Result<T, ClientError>>,
+) -> Result<Vec<T>, ClientError> {
+    stream.try_collect().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::{header::HeaderMap, StatusCode};
+    use std::{sync::Mutex, time::Duration};
+
+    type Page = (Vec<u32>, Option<String>);
+
+    /// A fetcher over the pages, which records the tokens it is called with.
+    fn pages<'a>(
+        pages: Vec<(Vec<u32>, Option<&'static str>)>,
+        tokens: &'a Mutex<Vec<Option<String>>>,
+    ) -> impl Fn(Option<String>) -> futures::future::Ready<Result<Page, ClientError>> + 'a {
+        move |token| {
+            let mut tokens = tokens.lock().unwrap();
+            let page = pages[tokens.len()].clone();
+            tokens.push(token);
+            futures::future::ready(Ok((page.0, page.1.map(str::to_string))))
+        }
+    }
+
+    #[tokio::test]
+    async fn follows_tokens_until_missing() {
+        let tokens = Mutex::new(Vec::new());
+        let fetch = pages(vec![(vec![1, 2], Some("p2")), (vec![3], None)], &tokens);
+
+        assert_eq!(collect_all(paginate(fetch)).await.unwrap(), vec![1, 2, 3]);
+        assert_eq!(*tokens.lock().unwrap(), vec![None, Some("p2".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn stops_at_empty_token() {
+        let tokens = Mutex::new(Vec::new());
+        let fetch = pages(vec![(vec![1], Some("")), (vec![2], None)], &tokens);
+
+        assert_eq!(collect_all(paginate(fetch)).await.unwrap(), vec![1]);
+        assert_eq!(tokens.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn collects_pages_with_metadata() {
+        let tokens = Mutex::new(Vec::new());
+        let fetch = pages(
+            vec![(vec![1], Some("p2")), (vec![2], Some("")), (vec![3], None)],
+            &tokens,
+        );
+
+        let pages = collect_pages(
+            |token| {
+                let fut = fetch(token);
+                async move {
+                    let (items, next) = fut.await?;
+                    Ok(ApiResponse {
+                        value: (items, next),
+                        status: StatusCode::OK,
+                        headers: HeaderMap::new(),
+                        request_id: None,
+                        elapsed: Duration::ZERO,
+                    })
+                }
+            },
+            |(_, next): &Page| next.clone(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            pages
+                .into_iter()
+                .map(|page| page.value.0)
+                .collect::<Vec<_>>(),
+            vec![vec![1], vec![2]]
+        );
+        assert_eq!(*tokens.lock().unwrap(), vec![None, Some("p2".to_string())]);
+    }
+}
@@ -0,0 +1,411 @@
+use crate::{
+    builder::ClientBuilder,
+    client::{Client, ClientError},
+    oauth::{ClientParameters, State},
+    ratelimit::ConcurrencyLimit,
+    resources::EventClient,
+    token::RefreshingToken,
+};
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+/// CredentialStore holds the ClientParameters of each user a ClientPool acts on behalf of.
+#[async_trait]
+pub trait CredentialStore: std::fmt::Debug + Send + Sync {
+    /// The parameters for the user. They must include a refresh token, a current access key, or
+    /// both.
+    async fn load(&self, user: &str) -> Result<ClientParameters, ClientError>;
+
+    /// Store the parameters, which may hold a refreshed access key, when the user's client leaves
+    /// the pool. The default does nothing.
+    async fn save(&self, _user: &str, _params: &ClientParameters) -> Result<(), ClientError> {
+        Ok(())
+    }
+}
+
+/// MemoryCredentials is a CredentialStore kept in memory. Clones share the same credentials.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryCredentials(Arc<std::sync::Mutex<HashMap<String, ClientParameters>>>);
+
+impl MemoryCredentials {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&self, user: impl Into<String>, params: ClientParameters) {
+        self.0.lock().unwrap().insert(user.into(), params);
+    }
+
+    pub fn remove(&self, user: &str) -> Option<ClientParameters> {
+        self.0.lock().unwrap().remove(user)
+    }
+}
+
+#[async_trait]
+impl CredentialStore for MemoryCredentials {
+    async fn load(&self, user: &str) -> Result<ClientParameters, ClientError> {
+        self.0
+            .lock()
+            .unwrap()
+            .get(user)
+            .cloned()
+            .ok_or_else(|| ClientError::UnknownError(format!("no credentials for user {}", user)))
+    }
+
+    async fn save(&self, user: &str, params: &ClientParameters) -> Result<(), ClientError> {
+        self.insert(user, params.clone());
+        Ok(())
+    }
+}
+
+type Configure = Arc<dyn Fn(ClientBuilder) -> ClientBuilder + Send + Sync>;
+
+/// Lease counts the clones of a pooled Client which are held outside the pool. Each clone of the
+/// Client clones its lease, and the count drops again as they are dropped.
+#[derive(Debug)]
+pub(crate) struct Lease(Arc<AtomicUsize>);
+
+impl Lease {
+    fn new(count: Arc<AtomicUsize>) -> Self {
+        count.fetch_add(1, Ordering::SeqCst);
+        Self(count)
+    }
+}
+
+impl Clone for Lease {
+    fn clone(&self) -> Self {
+        Self::new(self.0.clone())
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug)]
+struct PoolEntry {
+    client: Client,
+    state: State,
+    leases: Arc<AtomicUsize>,
+    last_used: Instant,
+}
+
+impl PoolEntry {
+    /// A clone of the client, counted as held until it and its clones are dropped.
+    fn lease(&mut self) -> Client {
+        self.last_used = Instant::now();
+        let mut client = self.client.clone();
+        client.set_lease(Lease::new(self.leases.clone()));
+        client
+    }
+}
+
+/// The clients in a pool, and the parameters of those which have been removed but are still
+/// being saved to the store. A client asked for in the meantime is built from the latter rather
+/// than from the stale parameters in the store.
+#[derive(Debug, Default)]
+struct Entries {
+    clients: HashMap<String, PoolEntry>,
+    saving: HashMap<String, State>,
+}
+
+/// ClientPool provides a Client for each user a service acts on behalf of. Clients are built from
+/// the CredentialStore the first time a user is asked for, and refresh that user's access token
+/// on their own with a RefreshingToken. Each user's requests are capped at max_concurrency in
+/// flight at once, across all clones of their client.
+///
+/// Clients which have not been asked for within the idle timeout, and are no longer held outside
+/// the pool, are removed by evict_idle, which should be called periodically. Their parameters are
+/// saved back to the store first, so a refreshed access key is not lost.
+///
+/// ```ignore
+/// let pool = ClientPool::new(credentials)
+///     .max_concurrency(2)
+///     .configure(|builder| builder.retry_policy(RetryPolicy::default()));
+///
+/// let events = pool.events("user@example.com").await?;
+/// let list = events.list("primary".to_string(), start, end).await?;
+/// ```
+#[derive(Clone)]
+pub struct ClientPool {
+    store: Arc<dyn CredentialStore>,
+    configure: Option<Configure>,
+    max_concurrency: usize,
+    idle_timeout: Duration,
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl std::fmt::Debug for ClientPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientPool")
+            .field("store", &self.store)
+            .field("max_concurrency", &self.max_concurrency)
+            .field("idle_timeout", &self.idle_timeout)
+            .finish()
+    }
+}
+
+impl ClientPool {
+    /// A pool of clients built from the store, allowing four requests in flight per user and
+    /// evicting clients after ten minutes without use.
+    pub fn new(store: impl CredentialStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+            configure: None,
+            max_concurrency: 4,
+            idle_timeout: Duration::from_secs(600),
+            entries: Default::default(),
+        }
+    }
+
+    /// The maximum number of requests in flight at once for each user.
+    pub fn max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency.max(1);
+        self
+    }
+
+    /// How long a client may go without being asked for before evict_idle removes it.
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Apply further configuration to each client before it is built, e.g. a retry policy or a
+    /// QuotaGovernor shared by the whole project.
+    pub fn configure(
+        mut self,
+        configure: impl Fn(ClientBuilder) -> ClientBuilder + Send + Sync + 'static,
+    ) -> Self {
+        self.configure = Some(Arc::new(configure));
+        self
+    }
+
+    /// The client for the user, building it if it is not already in the pool.
+    pub async fn client(&self, user: &str) -> Result<Client, ClientError> {
+        let saving = {
+            let mut entries = self.entries.lock().await;
+            if let Some(entry) = entries.clients.get_mut(user) {
+                return Ok(entry.lease());
+            }
+            entries.saving.get(user).cloned()
+        };
+
+        // the store is consulted without holding the lock, so a slow store does not hold up
+        // other users. If the user was added concurrently, that client is kept.
+        let built = match saving {
+            Some(state) => self.build(state)?,
+            None => self.build(Arc::new(Mutex::new(self.store.load(user).await?)))?,
+        };
+
+        let mut entries = self.entries.lock().await;
+        Ok(entries
+            .clients
+            .entry(user.to_string())
+            .or_insert(built)
+            .lease())
+    }
+
+    /// An EventClient acting on behalf of the user.
+    pub async fn events(&self, user: &str) -> Result<EventClient, ClientError> {
+        Ok(EventClient::new(self.client(user).await?))
+    }
+
+    /// The number of clients in the pool.
+    pub async fn len(&self) -> usize {
+        self.entries.lock().await.clients.len()
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.entries.lock().await.clients.is_empty()
+    }
+
+    /// Remove the user's client, saving its parameters to the store. Clones of the client which
+    /// are still held keep working. If saving fails, the client is returned to the pool.
+    pub async fn remove(&self, user: &str) -> Result<(), ClientError> {
+        let entry = self.take(|name, _| name == user).await.pop();
+
+        match entry {
+            Some((user, entry)) => self.save(user, entry).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Remove the clients which have been idle for longer than the idle timeout, saving their
+    /// parameters to the store, and return how many were removed. A client is only idle once no
+    /// clones of it are held elsewhere, so that a user never has two concurrency limits. Clients
+    /// whose parameters could not be saved are returned to the pool; the first failure is
+    /// returned.
+    pub async fn evict_idle(&self) -> Result<usize, ClientError> {
+        let idle = self
+            .take(|_, entry| {
+                entry.last_used.elapsed() >= self.idle_timeout
+                    && entry.leases.load(Ordering::SeqCst) == 0
+            })
+            .await;
+
+        let mut count = 0;
+        let mut error = None;
+        for (user, entry) in idle {
+            match self.save(user, entry).await {
+                Ok(()) => count += 1,
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+
+        error.map_or(Ok(count), Err)
+    }
+
+    /// Remove the matching entries from the pool, marking their parameters as being saved.
+    async fn take(&self, matches: impl Fn(&str, &PoolEntry) -> bool) -> Vec<(String, PoolEntry)> {
+        let mut entries = self.entries.lock().await;
+        let users = entries
+            .clients
+            .iter()
+            .filter(|(user, entry)| matches(user, entry))
+            .map(|(user, _)| user.clone())
+            .collect::<Vec<_>>();
+
+        users
+            .into_iter()
+            .filter_map(|user| {
+                let entry = entries.clients.remove(&user)?;
+                entries.saving.insert(user.clone(), entry.state.clone());
+                Some((user, entry))
+            })
+            .collect()
+    }
+
+    fn build(&self, state: State) -> Result<PoolEntry, ClientError> {
+        let limit = ConcurrencyLimit::new(self.max_concurrency);
+
+        let mut builder = ClientBuilder::with_token_provider(RefreshingToken::new(state.clone()));
+        if let Some(configure) = &self.configure {
+            builder = configure(builder);
+        }
+
+        Ok(PoolEntry {
+            client: builder.concurrency_limit(limit).build()?,
+            state,
+            leases: Default::default(),
+            last_used: Instant::now(),
+        })
+    }
+
+    /// Save the parameters of an entry taken from the pool. The state is locked without holding
+    /// the pool, since a RefreshingToken holds it while refreshing.
+    async fn save(&self, user: String, entry: PoolEntry) -> Result<(), ClientError> {
+        let params = entry.state.lock().await.clone();
+        let res = self.store.save(&user, &params).await;
+
+        let mut entries = self.entries.lock().await;
+        if entries
+            .saving
+            .get(&user)
+            .is_some_and(|state| Arc::ptr_eq(state, &entry.state))
+        {
+            entries.saving.remove(&user);
+        }
+
+        if res.is_err() {
+            entries.clients.entry(user).or_insert(entry);
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(access_key: &str) -> ClientParameters {
+        ClientParameters {
+            access_key: Some(access_key.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn pool() -> (MemoryCredentials, ClientPool) {
+        let store = MemoryCredentials::new();
+        store.insert("user", params("old"));
+        store.insert("other", params("other"));
+
+        let pool = ClientPool::new(store.clone()).idle_timeout(Duration::ZERO);
+        (store, pool)
+    }
+
+    async fn state(pool: &ClientPool, user: &str) -> State {
+        pool.entries.lock().await.clients[user].state.clone()
+    }
+
+    #[tokio::test]
+    async fn held_clients_are_not_evicted() {
+        let (_, pool) = pool();
+        let client = pool.client("user").await.unwrap();
+        let events = EventClient::new(client.clone());
+        drop(client);
+
+        assert_eq!(pool.evict_idle().await.unwrap(), 0);
+        assert_eq!(pool.len().await, 1);
+
+        drop(events);
+        assert_eq!(pool.evict_idle().await.unwrap(), 1);
+        assert!(pool.is_empty().await);
+    }
+
+    #[tokio::test]
+    async fn evicted_parameters_are_saved() {
+        let (store, pool) = pool();
+        drop(pool.client("user").await.unwrap());
+
+        state(&pool, "user").await.lock().await.access_key = Some("new".to_string());
+
+        assert_eq!(pool.evict_idle().await.unwrap(), 1);
+        assert_eq!(
+            store.remove("user").unwrap().access_key.as_deref(),
+            Some("new")
+        );
+    }
+
+    #[tokio::test]
+    async fn saving_does_not_hold_up_the_pool() {
+        let (store, pool) = pool();
+        drop(pool.client("user").await.unwrap());
+
+        // a refresh in progress holds the user's state.
+        let live = state(&pool, "user").await;
+        let refresh = live.lock().await;
+
+        let removing = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.remove("user").await }
+        });
+        tokio::task::yield_now().await;
+
+        // other users are served meanwhile, and the user is rebuilt from the live state rather
+        // than the stale parameters in the store.
+        drop(pool.client("other").await.unwrap());
+        drop(pool.client("user").await.unwrap());
+        assert!(Arc::ptr_eq(&live, &state(&pool, "user").await));
+
+        drop(refresh);
+        removing.await.unwrap().unwrap();
+        assert_eq!(
+            store.remove("user").unwrap().access_key.as_deref(),
+            Some("old")
+        );
+        assert!(pool.entries.lock().await.saving.is_empty());
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::Instant,
+};
+
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    tokens: f64,
+    per_sec: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_sec).min(self.capacity);
+        self.last = now;
+    }
+}
+
+/// RateLimiter is a token bucket. It starts full, allowing a burst of up to `requests` requests,
+/// and refills continuously at `requests` per `per`. Clones share the same bucket.
+#[derive(Debug, Clone)]
+pub struct RateLimiter(Arc<Mutex<Bucket>>);
+
+impl RateLimiter {
+    pub fn new(requests: u32, per: Duration) -> Self {
+        let capacity = f64::from(requests.max(1));
+
+        Self(Arc::new(Mutex::new(Bucket {
+            capacity,
+            tokens: capacity,
+            per_sec: capacity / per.as_secs_f64().max(f64::EPSILON),
+            last: Instant::now(),
+        })))
+    }
+
+    pub fn per_second(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(1))
+    }
+
+    pub fn per_minute(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(60))
+    }
+
+    /// Take a token without waiting. Returns the time until one is available on failure.
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        let mut bucket = self.0.lock().unwrap();
+        bucket.refill(Instant::now());
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / bucket.per_sec,
+            ))
+        }
+    }
+
+    /// Wait until a token is available and take it.
+    pub async fn acquire(&self) {
+        while let Err(wait) = self.try_acquire() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// QuotaGovernor throttles requests before they are sent so that a Google project's quotas are
+/// not exceeded. The project limiter should be shared by every client using the same project,
+/// while the user limiter applies to the clients acting on behalf of a single user. Either may be
+/// omitted.
+#[derive(Debug, Clone, Default)]
+pub struct QuotaGovernor {
+    project: Option<RateLimiter>,
+    user: Option<RateLimiter>,
+}
+
+impl QuotaGovernor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn project(mut self, limiter: RateLimiter) -> Self {
+        self.project = Some(limiter);
+        self
+    }
+
+    pub fn user(mut self, limiter: RateLimiter) -> Self {
+        self.user = Some(limiter);
+        self
+    }
+
+    /// Wait until both the user and project budgets permit another request.
+    pub async fn acquire(&self) {
+        if let Some(user) = &self.user {
+            user.acquire().await;
+        }
+
+        if let Some(project) = &self.project {
+            project.acquire().await;
+        }
+    }
+}
+
+/// ConcurrencyLimit caps the number of requests in flight at once. Unlike the RateLimiter, it
+/// does not limit how often requests are sent, only how many are waiting on a response. Clones
+/// share the same limit.
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimit {
+    semaphore: Arc<Semaphore>,
+    max: usize,
+}
+
+impl ConcurrencyLimit {
+    pub fn new(max: usize) -> Self {
+        let max = max.max(1);
+
+        Self {
+            semaphore: Arc::new(Semaphore::new(max)),
+            max,
+        }
+    }
+
+    /// The number of requests currently in flight.
+    pub fn in_flight(&self) -> usize {
+        self.max - self.semaphore.available_permits()
+    }
+
+    /// Wait until fewer than the maximum number of requests are in flight. The request counts
+    /// as in flight until the permit is dropped.
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        // the semaphore is never closed.
+        self.semaphore.clone().acquire_owned().await.unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn allows_a_burst_then_refills() {
+        let limiter = RateLimiter::per_second(4);
+        for _ in 0..4 {
+            assert!(limiter.try_acquire().is_ok());
+        }
+
+        assert_eq!(limiter.try_acquire(), Err(Duration::from_millis(250)));
+
+        tokio::time::advance(Duration::from_millis(100)).await;
+        assert_eq!(limiter.try_acquire(), Err(Duration::from_millis(150)));
+
+        tokio::time::advance(Duration::from_millis(150)).await;
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refill_is_capped_at_the_burst() {
+        let limiter = RateLimiter::per_second(2);
+        tokio::time::advance(Duration::from_secs(10)).await;
+
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_waits_for_a_token() {
+        let limiter = RateLimiter::per_minute(1);
+        let start = Instant::now();
+
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_secs(60));
+        assert!(start.elapsed() < Duration::from_secs(61));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn governor_waits_for_the_slower_budget() {
+        let governor = QuotaGovernor::new()
+            .user(RateLimiter::per_second(1))
+            .project(RateLimiter::per_second(10));
+        let start = Instant::now();
+
+        governor.acquire().await;
+        governor.acquire().await;
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        assert!(start.elapsed() < Duration::from_millis(1100));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn concurrency_limit_counts_permits() {
+        let limit = ConcurrencyLimit::new(2);
+        let first = limit.acquire().await;
+        let second = limit.clone().acquire().await;
+        assert_eq!(limit.in_flight(), 2);
+
+        let blocked = tokio::time::timeout(Duration::from_secs(1), limit.acquire()).await;
+        assert!(blocked.is_err());
+
+        drop(first);
+        assert_eq!(limit.in_flight(), 1);
+        let _third = limit.acquire().await;
+        assert_eq!(limit.in_flight(), 2);
+
+        drop(second);
+        assert_eq!(limit.in_flight(), 1);
+    }
+}
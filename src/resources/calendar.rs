@@ -1,6 +1,6 @@
 use crate::{
     resources::ConferenceProperties,
-    sendable::{QueryParams, Sendable},
+    sendable::{path_segment, QueryParams, Sendable},
 };
 use serde_derive::{Deserialize, Serialize};
 
@@ -29,8 +29,10 @@ pub struct Calendar {
 }
 
 impl Sendable for Calendar {
+    type Response = Calendar;
+
     fn path(&self, _action: Option<String>) -> String {
-        format!("calendars/{}", self.id)
+        format!("calendars/{}", path_segment(&self.id))
     }
 
     fn query(&self) -> QueryParams {
@@ -1,7 +1,10 @@
-use crate::client::{Client, ClientError};
+use crate::client::{ApiResponse, Client, ClientError};
+use crate::pagination::{collect_all, collect_pages, paginate};
 use crate::resources::{CalendarAccessRole, ConferenceProperties};
-use crate::sendable::{QueryParams, Sendable};
+use crate::sendable::{path_segment, Fields, QueryParams, Sendable};
 use crate::DefaultReminder;
+use futures::Stream;
+use reqwest::{header::HeaderMap, Method};
 use serde_derive::{Deserialize, Serialize};
 
 /*
@@ -10,7 +13,8 @@ use serde_derive::{Deserialize, Serialize};
 
 /// CalendarListClient is the method of accessing the calendar list. You must provide it with a
 /// Google Calendar client.
-pub struct CalendarListClient(Client);
+#[derive(Debug, Clone)]
+pub struct CalendarListClient(Client, Option<Fields>);
 
 fn default_entry_kind() -> Option<String> {
     Some("calendar#calendarListEntry".to_string())
@@ -28,15 +32,19 @@ pub struct CalendarListItem {
     #[serde(default = "default_entry_kind")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub kind: Option<String>,
+    #[serde(default)]
     pub id: String,
+    #[serde(default)]
     pub etag: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub location: Option<String>,
+    #[serde(default)]
     pub summary: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub summary_override: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub time_zone: Option<String>,
+    #[serde(default)]
     pub access_role: CalendarAccessRole,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub background_color: Option<String>,
@@ -56,7 +64,9 @@ pub struct CalendarListItem {
     pub selected: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    #[serde(default)]
     pub notification_settings: NotificationSettings,
+    #[serde(default)]
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub default_reminders: Vec<DefaultReminder>,
 
@@ -64,9 +74,10 @@ pub struct CalendarListItem {
     query_string: QueryParams,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct NotificationSettings {
+    #[serde(default)]
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub notifications: Vec<NotificationSetting>,
 }
@@ -102,9 +113,13 @@ pub struct CalendarList {
     #[serde(default = "default_list_kind")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub kind: Option<String>,
+    #[serde(default)]
     pub etag: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_page_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub next_sync_token: Option<String>,
+    #[serde(default)]
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub items: Vec<CalendarListItem>,
 
@@ -113,8 +128,10 @@ pub struct CalendarList {
 }
 
 impl Sendable for CalendarListItem {
+    type Response = CalendarListItem;
+
     fn path(&self, _action: Option<String>) -> String {
-        format!("users/me/calendarList/{}", self.id)
+        format!("users/me/calendarList/{}", path_segment(&self.id))
     }
 
     fn query(&self) -> QueryParams {
@@ -123,6 +140,8 @@ impl Sendable for CalendarListItem {
 }
 
 impl Sendable for CalendarList {
+    type Response = CalendarList;
+
     fn path(&self, _action: Option<String>) -> String {
         String::from("users/me/calendarList")
     }
@@ -135,22 +154,74 @@ impl Sendable for CalendarList {
 impl CalendarListClient {
     /// Construct a CalendarListClient. Requires a Google Calendar Client.
     pub fn new(client: Client) -> Self {
-        Self(client)
+        Self(client, None)
+    }
+
+    /// Only request the selected fields when listing calendars. Fields which are not selected are
+    /// left unset.
+    pub fn with_fields(mut self, fields: Fields) -> Self {
+        self.1 = Some(fields);
+        self
     }
 
-    /// List the calendars. Currently only returns the first page of results.
+    /// List the calendars. All pages are retrieved; see list_stream to process them as they
+    /// arrive.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "gcal.calendar_list.list", skip_all)
+    )]
     pub async fn list(&self) -> Result<Vec<CalendarListItem>, ClientError> {
-        // FIXME get all the results lol
+        collect_all(self.list_stream()).await
+    }
+
+    /// List the calendars as with list, returning each page along with the metadata of the
+    /// response it came from.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "gcal.calendar_list.list_with_meta", skip_all)
+    )]
+    pub async fn list_with_meta(&self) -> Result<Vec<ApiResponse<CalendarList>>, ClientError> {
+        collect_pages(
+            |page_token| {
+                let cl = self.list_target(page_token);
+                async move {
+                    self.0
+                        .request_with_meta(Method::GET, None, cl, HeaderMap::new())
+                        .await?
+                        .json()
+                }
+            },
+            |list: &CalendarList| list.next_page_token.clone(),
+        )
+        .await
+    }
+
+    fn list_target(&self, page_token: Option<String>) -> CalendarList {
         let mut cl = CalendarList::default();
         cl.query_string
             .insert("minAccessRole".to_string(), "owner".to_string());
+        if let Some(fields) = &self.1 {
+            cl.query_string
+                .insert("fields".to_string(), fields.list_mask());
+        }
+        if let Some(page_token) = page_token {
+            cl.query_string.insert("pageToken".to_string(), page_token);
+        }
+        cl
+    }
+
+    /// Stream the calendars, following nextPageToken until all pages have been retrieved.
+    pub fn list_stream(&self) -> impl Stream<Item = Result<CalendarListItem, ClientError>> {
+        let this = self.clone();
+
+        paginate(move |page_token| {
+            let client = this.0.clone();
+            let cl = this.list_target(page_token);
 
-        Ok(self
-            .0
-            .get(None, cl)
-            .await?
-            .json::<CalendarList>()
-            .await?
-            .items)
+            async move {
+                let list = client.get(None, cl).await?.json::<CalendarList>()?;
+                Ok((list.items, list.next_page_token))
+            }
+        })
     }
 }
@@ -0,0 +1,58 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/*
+ * from: https://developers.google.com/calendar/api/v3/reference/channels
+ */
+
+fn default_channel_kind() -> Option<String> {
+    Some("api#channel".to_string())
+}
+
+fn default_channel_type() -> String {
+    "web_hook".to_string()
+}
+
+/// Channel is a push notification channel, used to watch a collection for changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Channel {
+    #[serde(default = "default_channel_kind")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    /// A UUID or similar unique string chosen by the caller.
+    pub id: String,
+    #[serde(rename = "type", default = "default_channel_type")]
+    pub typ: String,
+    /// The HTTPS URL notifications are delivered to.
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Milliseconds since the epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiration: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_uri: Option<String>,
+}
+
+impl Channel {
+    /// A web hook channel delivering to the address, with a random ID.
+    pub fn web_hook(address: impl Into<String>) -> Self {
+        Self {
+            kind: default_channel_kind(),
+            id: uuid::Uuid::new_v4().to_string(),
+            typ: default_channel_type(),
+            address: address.into(),
+            token: None,
+            expiration: None,
+            params: BTreeMap::new(),
+            resource_id: None,
+            resource_uri: None,
+        }
+    }
+}
@@ -3,7 +3,7 @@ use serde_derive::{Deserialize, Serialize};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ConferenceProperties {
-    #[serde(rename = "allowedConferenceSolutionTypes")]
+    #[serde(rename = "allowedConferenceSolutionTypes", default)]
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub allowed_solution_types: Vec<AllowedSolutionType>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -0,0 +1,86 @@
+use crate::client::ClientError;
+
+/*
+ * from: https://developers.google.com/calendar/api/v3/reference/events/insert
+ *
+ * Event IDs chosen by the client must use the characters of base32hex encoding (lowercase a-v
+ * and digits 0-9) and be between 5 and 1024 characters long.
+ */
+
+pub const EVENT_ID_MIN_LEN: usize = 5;
+pub const EVENT_ID_MAX_LEN: usize = 1024;
+
+/// The namespace of the UUIDs derive_event_id produces, so the same key used for other purposes
+/// does not produce the same ID.
+const EVENT_ID_NAMESPACE: uuid::Uuid =
+    uuid::Uuid::from_u128(0x6763616c_6576_656e_7469_645f6e616d65);
+
+/// A new random event ID. A simple UUID is 32 lowercase hex digits, which is valid base32hex.
+pub fn generate_event_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+/// An event ID derived from the key, which is the same every time the same key is provided. Use
+/// something which identifies the event in your own system, such as a database key, so that
+/// creating it again can be detected; see EventClient::insert_idempotent.
+pub fn derive_event_id(key: impl AsRef<[u8]>) -> String {
+    uuid::Uuid::new_v5(&EVENT_ID_NAMESPACE, key.as_ref())
+        .simple()
+        .to_string()
+}
+
+/// Check that an event ID is acceptable to Google, returning ClientError::InvalidEventId if not.
+pub fn validate_event_id(id: &str) -> Result<(), ClientError> {
+    if id.len() < EVENT_ID_MIN_LEN || id.len() > EVENT_ID_MAX_LEN {
+        return Err(ClientError::InvalidEventId(format!(
+            "{:?} must be between {} and {} characters",
+            id, EVENT_ID_MIN_LEN, EVENT_ID_MAX_LEN
+        )));
+    }
+
+    if let Some(c) = id.chars().find(|c| !matches!(c, 'a'..='v' | '0'..='9')) {
+        return Err(ClientError::InvalidEventId(format!(
+            "{:?} contains {:?}; only lowercase a-v and 0-9 are allowed",
+            id, c
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn length_limits() {
+        assert!(validate_event_id(&"a".repeat(EVENT_ID_MIN_LEN - 1)).is_err());
+        assert!(validate_event_id(&"a".repeat(EVENT_ID_MIN_LEN)).is_ok());
+        assert!(validate_event_id(&"a".repeat(EVENT_ID_MAX_LEN)).is_ok());
+        assert!(matches!(
+            validate_event_id(&"a".repeat(EVENT_ID_MAX_LEN + 1)),
+            Err(ClientError::InvalidEventId(_))
+        ));
+    }
+
+    #[test]
+    fn base32hex_characters() {
+        assert!(validate_event_id("0123456789abcdefghijklmnopqrstuv").is_ok());
+        assert!(validate_event_id("abcdw").is_err());
+        assert!(validate_event_id("ABCDE").is_err());
+        assert!(validate_event_id("abc-de").is_err());
+    }
+
+    #[test]
+    fn generated_ids_are_valid() {
+        assert!(validate_event_id(&generate_event_id()).is_ok());
+        assert_ne!(generate_event_id(), generate_event_id());
+    }
+
+    #[test]
+    fn derived_ids_are_stable() {
+        assert_eq!(derive_event_id("order-1"), derive_event_id("order-1"));
+        assert_ne!(derive_event_id("order-1"), derive_event_id("order-2"));
+        assert!(validate_event_id(&derive_event_id("order-1")).is_ok());
+    }
+}
@@ -1,8 +1,13 @@
 use crate::{
-    client::{Client, ClientError},
-    resources::{CalendarAccessRole, DefaultReminder, SendUpdates},
-    sendable::{AdditionalProperties, QueryParams, Sendable},
+    client::{with_meta, ApiResponse, Client, ClientError, Conditional, Precondition},
+    pagination::{collect_all, collect_pages, paginate},
+    resources::{
+        generate_event_id, validate_event_id, CalendarAccessRole, DefaultReminder, SendUpdates,
+    },
+    sendable::{path_segment, AdditionalProperties, Fields, QueryParams, Sendable},
 };
+use futures::Stream;
+use reqwest::{header::HeaderMap, Method, StatusCode};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeSet;
 
@@ -18,13 +23,10 @@ fn default_events_kind() -> Option<String> {
     Some("calendar#events".to_string())
 }
 
-fn default_true() -> Option<bool> {
-    Some(true)
-}
-
 /// EventClient is the method of managing events from a specific calendar. Requires a Google
 /// Calendar client.
-pub struct EventClient(Client);
+#[derive(Debug, Clone)]
+pub struct EventClient(Client, Option<Fields>);
 
 /// Events is a listing of events on a per-page basis.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -33,16 +35,24 @@ pub struct Events {
     #[serde(default = "default_events_kind")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub kind: Option<String>,
+    #[serde(default)]
     pub etag: String,
+    #[serde(default)]
     pub summary: String,
+    #[serde(default)]
     pub description: String,
+    #[serde(default)]
     pub updated: String,
+    #[serde(default)]
     pub time_zone: String,
+    #[serde(default)]
     pub access_role: CalendarAccessRole,
+    #[serde(default)]
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub default_reminders: Vec<DefaultReminder>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub next_page_token: Option<String>,
+    #[serde(default)]
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub items: Vec<Event>,
 }
@@ -86,12 +96,15 @@ pub struct Event {
     pub extended_properties: Option<EventExtendedProperties>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub gadget: Option<EventGadget>,
-    #[serde(rename = "guestsCanInviteOthers", default = "default_true")]
+    /// Google omits this when it is true, so it is None both then and when a field mask leaves
+    /// it out; it no longer decodes as Some(true). See Event::guests_can_invite_others.
+    #[serde(rename = "guestsCanInviteOthers")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub guests_invite_others: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub guests_can_modify: Option<bool>,
-    #[serde(default = "default_true")]
+    /// Google omits this when it is true, so it is None both then and when a field mask leaves
+    /// it out; it no longer decodes as Some(true). See Event::guests_see_other_guests.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub guests_can_see_other_guests: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -139,6 +152,20 @@ pub struct Event {
     query_string: QueryParams,
 }
 
+impl Event {
+    /// Whether guests may invite others, applying Google's default of true when the field is
+    /// unset. Only meaningful if the field was requested.
+    pub fn guests_can_invite_others(&self) -> bool {
+        self.guests_invite_others.unwrap_or(true)
+    }
+
+    /// Whether guests may see the other guests, applying Google's default of true when the field
+    /// is unset. Only meaningful if the field was requested.
+    pub fn guests_see_other_guests(&self) -> bool {
+        self.guests_can_see_other_guests.unwrap_or(true)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub enum EventOfficeLocationType {
@@ -149,7 +176,7 @@ pub enum EventOfficeLocationType {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct EventOfficeLocation {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub building_id: Option<String>,
@@ -210,14 +237,14 @@ pub enum EventStatus {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct EventSource {
     pub title: String,
     pub url: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct EventReminder {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub overrides: Option<Vec<DefaultReminder>>,
@@ -225,7 +252,7 @@ pub struct EventReminder {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct EventOrganizer {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub display_name: Option<String>,
@@ -245,7 +272,7 @@ pub enum EventGadgetDisplay {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct EventGadget {
     pub display: EventGadgetDisplay,
     pub preferences: AdditionalProperties,
@@ -283,7 +310,7 @@ pub struct EventCalendarDate {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct EventConferenceData {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub conference_id: Option<String>,
@@ -314,7 +341,7 @@ pub struct EventCreator {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct EventConferenceEntryPoint {
     entry_point_type: EventConferenceEntryPointType,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -342,7 +369,7 @@ pub enum EventConferenceEntryPointType {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct EventCreateConferenceRequest {
     conference_solution_key: EventConferenceSolutionKey,
     request_id: String,
@@ -350,7 +377,7 @@ pub struct EventCreateConferenceRequest {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct EventConferenceStatus {
     status_code: EventConferenceStatusCode,
 }
@@ -365,14 +392,14 @@ pub enum EventConferenceStatusCode {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct EventConferenceSolution {
     pub icon_uri: String,
     pub key: EventConferenceSolutionKey,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct EventConferenceSolutionKey {
     #[serde(rename = "type")]
     pub typ: EventConferenceSolutionKeyType,
@@ -391,7 +418,7 @@ pub enum EventConferenceSolutionKeyType {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct EventAttendees {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub additional_guests: Option<u8>,
@@ -425,7 +452,7 @@ pub enum EventResponseStatus {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct EventAttachment {
     pub file_id: String,
     pub file_url: String,
@@ -435,14 +462,16 @@ pub struct EventAttachment {
 }
 
 impl Sendable for Event {
+    type Response = Event;
+
     fn path(&self, action: Option<String>) -> String {
         format!(
             "calendars/{}/events{}{}",
-            self.calendar_id.clone().unwrap(),
+            path_segment(self.calendar_id.as_deref().unwrap()),
             self.id
-                .clone()
-                .map_or_else(|| String::new(), |x| format!("/{}", x)),
-            action.map_or_else(|| String::new(), |x| format!("/{}", x))
+                .as_deref()
+                .map_or_else(String::new, |x| format!("/{}", path_segment(x))),
+            action.map_or_else(String::new, |x| format!("/{}", x))
         )
     }
 
@@ -451,115 +480,578 @@ impl Sendable for Event {
     }
 }
 
+/// A new event, posted to its calendar's collection with the ID, if any, in the body.
+#[derive(Serialize)]
+#[serde(transparent)]
+struct NewEvent(Event);
+
+impl Sendable for NewEvent {
+    type Response = Event;
+
+    fn path(&self, _action: Option<String>) -> String {
+        format!(
+            "calendars/{}/events",
+            path_segment(self.0.calendar_id.as_deref().unwrap())
+        )
+    }
+
+    fn query(&self) -> QueryParams {
+        self.0.query_string.clone()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+}
+
+fn set_supports_attachments(event: &mut Event) {
+    if event.attachments.as_ref().is_some_and(|a| !a.is_empty()) {
+        event
+            .query_string
+            .insert("supportsAttachments".to_string(), "true".to_string());
+    }
+}
+
+fn etag_of(event: &Event) -> Result<String, ClientError> {
+    event
+        .etag
+        .clone()
+        .ok_or_else(|| ClientError::UnknownError("event has no etag".to_string()))
+}
+
 impl EventClient {
     /// Construct a new EventClient. Requires a Google Calendar Client.
     pub fn new(client: Client) -> Self {
-        Self(client)
+        Self(client, None)
     }
 
-    /// Delete the event.
-    pub async fn delete(&self, event: Event) -> Result<(), ClientError> {
-        self.0.delete(None, event).await?;
-        Ok(())
+    /// Only request the selected fields when getting and listing events. Fields which are not
+    /// selected are left unset.
+    pub fn with_fields(mut self, fields: Fields) -> Self {
+        self.1 = Some(fields);
+        self
     }
 
-    /// Get an event by ID.
-    pub async fn get(&self, calendar_id: String, event_id: String) -> Result<Event, ClientError> {
-        let mut event = Event::default();
-        event.id = Some(event_id);
-        event.calendar_id = Some(calendar_id);
-        let resp = self.0.get(None, event).await?;
+    fn set_fields(&self, event: &mut Event, list: bool) {
+        if let Some(fields) = &self.1 {
+            let mask = if list {
+                fields.list_mask()
+            } else {
+                fields.mask()
+            };
 
-        Ok(resp.json().await?)
+            event.query_string.insert("fields".to_string(), mask);
+        }
     }
 
-    /// Import an event. See the Google Calendar documentation for the differences between import
-    /// and insert.
-    pub async fn import(&self, event: Event) -> Result<Event, ClientError> {
-        let resp = self.0.post(Some("import".to_string()), event).await?;
+    with_meta! {
+        /// Delete the event.
+        #[span = "gcal.events.delete"]
+        pub async fn delete(&self, event: Event) -> () as delete_with_meta {
+            Ok(self
+                .0
+                .request_with_meta(Method::DELETE, None, event, HeaderMap::new())
+                .await?
+                .map(|_| ()))
+        }
+    }
 
-        Ok(resp.json().await?)
+    with_meta! {
+        /// Delete the event only if it has not changed since it was retrieved, according to its
+        /// etag. Returns ClientError::PreconditionFailed if it has.
+        #[span = "gcal.events.delete_if_match"]
+        pub async fn delete_if_match(&self, event: Event) -> () as delete_if_match_with_meta {
+            let headers = Precondition::IfMatch(etag_of(&event)?).headers()?;
+            Ok(self
+                .0
+                .request_with_meta(Method::DELETE, None, event, headers)
+                .await?
+                .map(|_| ()))
+        }
     }
 
-    /// Insert an event. See the Google Calendar documentation for the differences between import
-    /// and insert.
-    pub async fn insert(&self, mut event: Event) -> Result<Event, ClientError> {
-        if let Some(attachments) = event.attachments.clone() {
-            if !attachments.is_empty() {
-                event
-                    .query_string
-                    .insert("supportsAttachments".to_string(), "true".to_string());
-            }
+    with_meta! {
+        /// Get an event by ID.
+        #[span = "gcal.events.get"]
+        pub async fn get(&self, calendar_id: String, event_id: String) -> Event as get_with_meta {
+            let mut event = Event {
+                id: Some(event_id),
+                calendar_id: Some(calendar_id),
+                ..Default::default()
+            };
+            self.set_fields(&mut event, false);
+
+            self.0
+                .request_with_meta(Method::GET, None, event, HeaderMap::new())
+                .await?
+                .json()
         }
+    }
 
-        let resp = self.0.post(Some("import".to_string()), event).await?;
+    with_meta! {
+        /// Get an event by ID, unless its etag still matches the one provided.
+        #[span = "gcal.events.get_if_none_match"]
+        pub async fn get_if_none_match(
+            &self,
+            calendar_id: String,
+            event_id: String,
+            etag: String,
+        ) -> Conditional<Event> as get_if_none_match_with_meta {
+            let mut event = Event {
+                id: Some(event_id),
+                calendar_id: Some(calendar_id),
+                ..Default::default()
+            };
+            self.set_fields(&mut event, false);
+            let headers = Precondition::IfNoneMatch(etag).headers()?;
 
-        Ok(resp.json().await?)
+            self.0
+                .request_with_meta(Method::GET, None, event, headers)
+                .await?
+                .try_map(|resp| {
+                    if resp.status() == StatusCode::NOT_MODIFIED {
+                        Ok(Conditional::NotModified)
+                    } else {
+                        Ok(Conditional::Modified(resp.json()?))
+                    }
+                })
+        }
     }
 
-    /// Retrieve all instances for a recurring event.
-    pub async fn instances(&self, event: Event) -> Result<Events, ClientError> {
-        Ok(self
-            .0
-            .get(Some("instances".to_string()), event)
-            .await?
-            .json()
-            .await?)
+    with_meta! {
+        /// Import an event. See the Google Calendar documentation for the differences between
+        /// import and insert.
+        #[span = "gcal.events.import"]
+        pub async fn import(&self, event: Event) -> Event as import_with_meta {
+            self.0
+                .request_with_meta(
+                    Method::POST,
+                    Some("import".to_string()),
+                    event,
+                    HeaderMap::new(),
+                )
+                .await?
+                .json()
+        }
     }
 
-    /// List events between the start and end times.
+    with_meta! {
+        /// Insert an event. See the Google Calendar documentation for the differences between
+        /// import and insert.
+        #[span = "gcal.events.insert"]
+        pub async fn insert(&self, event: Event) -> Event as insert_with_meta {
+            let mut event = event;
+            set_supports_attachments(&mut event);
+
+            self.0
+                .request_with_meta(
+                    Method::POST,
+                    Some("import".to_string()),
+                    event,
+                    HeaderMap::new(),
+                )
+                .await?
+                .json()
+        }
+    }
+
+    with_meta! {
+        /// Insert an event so that it is safe to retry, for instance after a timeout. The event
+        /// is given the ID provided, such as one from derive_event_id, or else keeps its own ID or
+        /// is given a new one. If the API reports a conflict because an earlier attempt already
+        /// created the event, the existing event is returned instead of an error.
+        ///
+        /// The conflict is returned as ClientError::Conflict if the existing event was cancelled
+        /// or does not match the one inserted, since the ID then belongs to some other event.
+        #[span = "gcal.events.insert_idempotent"]
+        pub async fn insert_idempotent(
+            &self,
+            event: Event,
+            id: Option<String>,
+        ) -> Event as insert_idempotent_with_meta {
+            let mut event = event;
+            let calendar_id = event.calendar_id.clone().ok_or_else(|| {
+                ClientError::UnknownError("event has no calendar id".to_string())
+            })?;
+
+            let id = match id.or_else(|| event.id.clone()) {
+                Some(id) => {
+                    validate_event_id(&id)?;
+                    id
+                }
+                None => generate_event_id(),
+            };
+            event.id = Some(id.clone());
+            set_supports_attachments(&mut event);
+
+            match self.0.send_with_meta(NewEvent(event.clone())).await {
+                Err(ClientError::Conflict(err)) => {
+                    // fetched without the field mask, which may leave out what is compared.
+                    let existing = Event {
+                        id: Some(id),
+                        calendar_id: Some(calendar_id),
+                        ..Default::default()
+                    };
+
+                    match self.0.send_with_meta(existing).await {
+                        Ok(existing) if is_retry_of(&event, &existing.value) => Ok(existing),
+                        _ => Err(ClientError::Conflict(err)),
+                    }
+                }
+                res => res,
+            }
+        }
+    }
+
+    with_meta! {
+        /// Retrieve all instances for a recurring event.
+        #[span = "gcal.events.instances"]
+        pub async fn instances(&self, event: Event) -> Events as instances_with_meta {
+            let mut event = event;
+            self.set_fields(&mut event, true);
+
+            self.0
+                .request_with_meta(
+                    Method::GET,
+                    Some("instances".to_string()),
+                    event,
+                    HeaderMap::new(),
+                )
+                .await?
+                .json()
+        }
+    }
+
+    /// List events between the start and end times. All pages are retrieved; see list_stream to
+    /// process them as they arrive.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "gcal.events.list", skip_all)
+    )]
     pub async fn list(
         &self,
         calendar_id: String,
         start_time: chrono::DateTime<chrono::Local>,
         end_time: chrono::DateTime<chrono::Local>,
     ) -> Result<Vec<Event>, ClientError> {
-        let mut event = Event::default();
-        event
-            .query_string
-            .insert("timeMin".to_string(), start_time.to_rfc3339());
-        event
-            .query_string
-            .insert("timeMax".to_string(), end_time.to_rfc3339());
-        event.calendar_id = Some(calendar_id);
+        collect_all(self.list_stream(calendar_id, start_time, end_time)).await
+    }
 
-        Ok(self.0.get(None, event).await?.json::<Events>().await?.items)
+    /// List events between the start and end times as with list, returning each page along with
+    /// the metadata of the response it came from.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "gcal.events.list_with_meta", skip_all)
+    )]
+    pub async fn list_with_meta(
+        &self,
+        calendar_id: String,
+        start_time: chrono::DateTime<chrono::Local>,
+        end_time: chrono::DateTime<chrono::Local>,
+    ) -> Result<Vec<ApiResponse<Events>>, ClientError> {
+        let mut template = Event::default();
+        self.set_fields(&mut template, true);
+
+        collect_pages(
+            |page_token| {
+                let event = list_target(&template, &calendar_id, start_time, end_time, page_token);
+                async move {
+                    self.0
+                        .request_with_meta(Method::GET, None, event, HeaderMap::new())
+                        .await?
+                        .json()
+                }
+            },
+            |events: &Events| events.next_page_token.clone(),
+        )
+        .await
     }
 
-    /// Move event to another destination calendar_id.
-    pub async fn move_to_calendar(
+    /// Stream events between the start and end times, following nextPageToken until all pages
+    /// have been retrieved.
+    pub fn list_stream(
         &self,
-        mut event: Event,
-        destination: String,
-        send_updates: Option<SendUpdates>,
-    ) -> Result<(), ClientError> {
+        calendar_id: String,
+        start_time: chrono::DateTime<chrono::Local>,
+        end_time: chrono::DateTime<chrono::Local>,
+    ) -> impl Stream<Item = Result<Event, ClientError>> {
+        let client = self.0.clone();
+        let mut template = Event::default();
+        self.set_fields(&mut template, true);
+
+        paginate(move |page_token| {
+            let client = client.clone();
+            let event = list_target(&template, &calendar_id, start_time, end_time, page_token);
+
+            async move {
+                let events = client.get(None, event).await?.json::<Events>()?;
+                Ok((events.items, events.next_page_token))
+            }
+        })
+    }
+
+    with_meta! {
+        /// Move event to another destination calendar_id.
+        #[span = "gcal.events.move_to_calendar"]
+        pub async fn move_to_calendar(
+            &self,
+            event: Event,
+            destination: String,
+            send_updates: Option<SendUpdates>,
+        ) -> () as move_to_calendar_with_meta {
+            let mut event = event;
+            event
+                .query_string
+                .insert("destination".to_string(), destination);
+            event.query_string.insert(
+                "sendUpdates".to_string(),
+                send_updates.map_or_else(|| "false".to_string(), |x| x.to_string()),
+            );
+
+            Ok(self
+                .0
+                .request_with_meta(
+                    Method::POST,
+                    Some("move".to_string()),
+                    event,
+                    HeaderMap::new(),
+                )
+                .await?
+                .map(|_| ()))
+        }
+    }
+
+    with_meta! {
+        /// Add an event with the summary.
+        #[span = "gcal.events.add"]
+        pub async fn add(&self, text: String) -> Event as add_with_meta {
+            let mut event = Event::default();
+            event.query_string.insert("text".to_string(), text);
+
+            self.0
+                .request_with_meta(
+                    Method::POST,
+                    Some("quickAdd".to_string()),
+                    event,
+                    HeaderMap::new(),
+                )
+                .await?
+                .json()
+        }
+    }
+
+    with_meta! {
+        /// Update an event.
+        #[span = "gcal.events.update"]
+        pub async fn update(&self, event: Event) -> Event as update_with_meta {
+            self.0
+                .request_with_meta(Method::PUT, None, event, HeaderMap::new())
+                .await?
+                .json()
+        }
+    }
+
+    with_meta! {
+        /// Update an event only if it has not changed since it was retrieved, according to its
+        /// etag. Returns ClientError::PreconditionFailed if it has.
+        #[span = "gcal.events.update_if_match"]
+        pub async fn update_if_match(&self, event: Event) -> Event as update_if_match_with_meta {
+            let headers = Precondition::IfMatch(etag_of(&event)?).headers()?;
+            self.0
+                .request_with_meta(Method::PUT, None, event, headers)
+                .await?
+                .json()
+        }
+    }
+}
+
+/// Whether the existing event is the one inserted, created by an earlier attempt, rather than
+/// a cancelled or unrelated event with the same ID.
+fn is_retry_of(inserted: &Event, existing: &Event) -> bool {
+    !matches!(existing.status, Some(EventStatus::Cancelled))
+        && inserted.summary == existing.summary
+        && inserted.description == existing.description
+        && inserted.location == existing.location
+        && same_date(&inserted.start, &existing.start)
+        && same_date(&inserted.end, &existing.end)
+}
+
+/// Compare dates, treating times which are the same instant in different offsets as equal.
+fn same_date(a: &Option<EventCalendarDate>, b: &Option<EventCalendarDate>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            let date_time_eq = match (&a.date_time, &b.date_time) {
+                (Some(x), Some(y)) => match (
+                    chrono::DateTime::parse_from_rfc3339(x),
+                    chrono::DateTime::parse_from_rfc3339(y),
+                ) {
+                    (Ok(x), Ok(y)) => x == y,
+                    _ => x == y,
+                },
+                (x, y) => x == y,
+            };
+
+            a.date == b.date && date_time_eq
+        }
+        (a, b) => a.is_none() && b.is_none(),
+    }
+}
+
+/// Build the request for one page of a listing between the start and end times.
+fn list_target(
+    template: &Event,
+    calendar_id: &str,
+    start_time: chrono::DateTime<chrono::Local>,
+    end_time: chrono::DateTime<chrono::Local>,
+    page_token: Option<String>,
+) -> Event {
+    let mut event = template.clone();
+    event
+        .query_string
+        .insert("timeMin".to_string(), start_time.to_rfc3339());
+    event
+        .query_string
+        .insert("timeMax".to_string(), end_time.to_rfc3339());
+    if let Some(page_token) = page_token {
         event
             .query_string
-            .insert("destination".to_string(), destination);
-        event.query_string.insert(
-            "sendUpdates".to_string(),
-            send_updates.map_or_else(|| "false".to_string(), |x| x.to_string()),
-        );
+            .insert("pageToken".to_string(), page_token);
+    }
+    event.calendar_id = Some(calendar_id.to_string());
+    event
+}
 
-        self.0.post(Some("move".to_string()), event).await?;
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::fake::FakeTransport;
+
+    #[test]
+    fn decodes_partial_nested_fields() {
+        let event: Event = serde_json::from_str(
+            r#"{
+                "id": "e1",
+                "organizer": { "displayName": "Erik" },
+                "attendees": [{ "displayName": "Guest" }],
+                "reminders": { "overrides": [] },
+                "source": { "url": "https://example.com" },
+                "conferenceData": { "conferenceId": "abc" },
+                "gadget": {}
+            }"#,
+        )
+        .unwrap();
+
+        let organizer = event.organizer.unwrap();
+        assert_eq!(organizer.display_name.as_deref(), Some("Erik"));
+        assert_eq!(organizer.email, "");
+        assert_eq!(event.attendees.unwrap()[0].email, "");
+        assert!(!event.reminders.unwrap().use_default);
+        assert_eq!(event.source.unwrap().title, "");
+        assert!(event.conference_data.unwrap().entry_points.is_empty());
+    }
+
+    #[test]
+    fn leaves_masked_out_fields_unset() {
+        let event: Event = serde_json::from_str(r#"{ "id": "e1" }"#).unwrap();
+
+        assert_eq!(event.guests_invite_others, None);
+        assert_eq!(event.guests_can_see_other_guests, None);
     }
 
-    /// Add an event with the summary.
-    pub async fn add(&self, text: String) -> Result<Event, ClientError> {
-        let mut event = Event::default();
-        event.query_string.insert("text".to_string(), text);
+    #[test]
+    fn omitted_guest_permissions_default_to_true() {
+        let event: Event =
+            serde_json::from_str(r#"{ "id": "e1", "guestsCanInviteOthers": false }"#).unwrap();
 
-        Ok(self
-            .0
-            .post(Some("quickAdd".to_string()), event)
-            .await?
-            .json()
-            .await?)
+        assert!(!event.guests_can_invite_others());
+        assert!(event.guests_see_other_guests());
     }
 
-    /// Update an event.
-    pub async fn update(&self, event: Event) -> Result<Event, ClientError> {
-        Ok(self.0.put(None, event).await?.json().await?)
+    fn event(summary: &str, date_time: &str) -> Event {
+        Event {
+            summary: Some(summary.to_string()),
+            start: Some(EventCalendarDate {
+                date_time: Some(date_time.to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matches_earlier_insert() {
+        let inserted = event("lunch", "2024-01-01T12:00:00Z");
+        let mut existing = event("lunch", "2024-01-01T07:00:00-05:00");
+        existing.status = Some(EventStatus::Confirmed);
+
+        assert!(is_retry_of(&inserted, &existing));
+    }
+
+    #[test]
+    fn rejects_cancelled_or_different_events() {
+        let inserted = event("lunch", "2024-01-01T12:00:00Z");
+
+        let mut cancelled = inserted.clone();
+        cancelled.status = Some(EventStatus::Cancelled);
+        assert!(!is_retry_of(&inserted, &cancelled));
+
+        assert!(!is_retry_of(
+            &inserted,
+            &event("dinner", "2024-01-01T12:00:00Z")
+        ));
+        assert!(!is_retry_of(
+            &inserted,
+            &event("lunch", "2024-01-01T13:00:00Z")
+        ));
+    }
+
+    #[test]
+    fn paths_encode_ids() {
+        let mut event = Event {
+            calendar_id: Some("en.usa#holiday@group.v.calendar.google.com".to_string()),
+            id: Some("a/b".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            event.path(Some("move".to_string())),
+            "calendars/en.usa%23holiday@group.v.calendar.google.com/events/a%2Fb/move"
+        );
+
+        event.id = None;
+        assert_eq!(event.path(None), NewEvent(event).path(None));
+    }
+
+    #[tokio::test]
+    async fn insert_conflicts_are_checked_without_the_field_mask() {
+        let transport = FakeTransport::new();
+        transport.respond_json(
+            StatusCode::CONFLICT,
+            r#"{"error":{"code":409,"message":"The requested identifier already exists.","errors":[{"reason":"duplicate"}]}}"#,
+        );
+        transport.respond_json(
+            StatusCode::OK,
+            r#"{"id":"abc123","summary":"lunch","start":{"dateTime":"2024-01-01T12:00:00Z"}}"#,
+        );
+
+        let mut client = Client::new("token".to_string()).unwrap();
+        client.set_transport(transport.clone());
+        let events = EventClient::new(client).with_fields(Fields::new().field("id"));
+
+        let mut inserted = event("lunch", "2024-01-01T12:00:00Z");
+        inserted.calendar_id = Some("primary".to_string());
+        let existing = events
+            .insert_idempotent(inserted, Some("abc123".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(existing.id.as_deref(), Some("abc123"));
+
+        let requests = transport.requests();
+        assert_eq!(requests[1].method, Method::GET);
+        assert!(requests[1]
+            .url
+            .path()
+            .ends_with("/calendars/primary/events/abc123"));
+        assert!(!requests[1].url.query_pairs().any(|(k, _)| k == "fields"));
     }
 }
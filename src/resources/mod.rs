@@ -3,11 +3,20 @@ pub mod calendar_list;
 pub use calendar_list::*;
 pub mod calendar;
 pub use calendar::*;
+/// Push notification channels, for watching collections.
+pub mod channel;
+pub use channel::*;
 pub mod conference_properties;
 pub use conference_properties::*;
 /// Events, the method you will work with most events in a single calendar.
 pub mod events;
 pub use events::*;
+/// Client-generated event IDs, for inserting events safely more than once.
+pub mod event_id;
+pub use event_id::*;
+/// A uniform client for any resource collection.
+pub mod resource_client;
+pub use resource_client::*;
 
 use serde_derive::{Deserialize, Serialize};
 
@@ -20,14 +29,13 @@ pub enum SendUpdates {
     None,
 }
 
-impl ToString for SendUpdates {
-    fn to_string(&self) -> String {
-        match self {
+impl std::fmt::Display for SendUpdates {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
             Self::All => "all",
             Self::ExternalOnly => "externalOnly",
             Self::None => "none",
-        }
-        .to_string()
+        })
     }
 }
 
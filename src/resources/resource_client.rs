@@ -0,0 +1,401 @@
+use crate::{
+    client::{ApiResponse, Client, ClientError},
+    pagination::{collect_all, collect_pages, paginate},
+    resources::{Calendar, CalendarListItem, Channel, Event},
+    sendable::{path_segment, Fields, QueryParams, Sendable},
+};
+use async_trait::async_trait;
+use futures::Stream;
+use reqwest::Method;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_derive::Deserialize;
+use std::marker::PhantomData;
+
+/// Resource describes a Google Calendar collection for ResourceClient. Collections which belong
+/// to another resource, such as the events of a calendar, are given that resource's ID as the
+/// parent; for top-level collections the parent is empty.
+pub trait Resource: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// The path of the collection relative to the API root, e.g. `calendars/{parent}/events`.
+    /// The parent is given already percent-encoded.
+    fn collection_path(parent: &str) -> String;
+
+    /// The ID of this resource, if it has been assigned one.
+    fn resource_id(&self) -> Option<String>;
+
+    /// Record the parent on a resource returned by the API, for resources which keep it.
+    fn set_parent(&mut self, _parent: &str) {}
+}
+
+/// ListableResource marks a Resource whose collection may be listed and watched, providing
+/// ResourceListApi for it.
+pub trait ListableResource: Resource {}
+
+impl Resource for Event {
+    fn collection_path(parent: &str) -> String {
+        format!("calendars/{}/events", parent)
+    }
+
+    fn resource_id(&self) -> Option<String> {
+        self.id.clone()
+    }
+
+    fn set_parent(&mut self, parent: &str) {
+        self.calendar_id = Some(parent.to_string())
+    }
+}
+
+impl ListableResource for Event {}
+
+impl Resource for CalendarListItem {
+    fn collection_path(_parent: &str) -> String {
+        "users/me/calendarList".to_string()
+    }
+
+    fn resource_id(&self) -> Option<String> {
+        (!self.id.is_empty()).then(|| self.id.clone())
+    }
+}
+
+impl ListableResource for CalendarListItem {}
+
+/// Calendars cannot be listed or watched, so Calendar is not a ListableResource; use the
+/// calendar list for that.
+impl Resource for Calendar {
+    fn collection_path(_parent: &str) -> String {
+        "calendars".to_string()
+    }
+
+    fn resource_id(&self) -> Option<String> {
+        (!self.id.is_empty()).then(|| self.id.clone())
+    }
+}
+
+/// ResourceApi is the uniform set of operations on a collection. ResourceClient implements it
+/// against the API; mocks may implement it for tests.
+#[async_trait]
+pub trait ResourceApi<R: Resource> {
+    async fn get(&self, id: &str) -> Result<R, ClientError>;
+    async fn insert(&self, resource: R) -> Result<R, ClientError>;
+    /// Replace the resource with the one provided, which must have an ID.
+    async fn update(&self, resource: R) -> Result<R, ClientError>;
+    /// Change only the fields present in the patch, a partial JSON representation.
+    async fn patch(&self, id: &str, patch: serde_json::Value) -> Result<R, ClientError>;
+    async fn delete(&self, id: &str) -> Result<(), ClientError>;
+}
+
+/// ResourceListApi holds the operations on the collection as a whole, for the resources which
+/// support them.
+#[async_trait]
+pub trait ResourceListApi<R: ListableResource>: ResourceApi<R> {
+    /// List the collection, retrieving all pages. query holds additional parameters, such as
+    /// timeMin for events.
+    async fn list(&self, query: QueryParams) -> Result<Vec<R>, ClientError>;
+    /// Start delivering change notifications for the collection to the channel.
+    async fn watch(&self, channel: Channel) -> Result<Channel, ClientError>;
+}
+
+/// A request for a path within a collection. Only the body is serialized.
+struct Target<B, T> {
+    method: Method,
+    path: String,
+    query: QueryParams,
+    body: B,
+    response: PhantomData<fn() -> T>,
+}
+
+impl<B: Serialize, T> Serialize for Target<B, T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.body.serialize(serializer)
+    }
+}
+
+impl<B: Serialize, T: DeserializeOwned> Sendable for Target<B, T> {
+    type Response = T;
+
+    fn path(&self, action: Option<String>) -> String {
+        match action {
+            Some(action) => format!("{}/{}", self.path, action),
+            None => self.path.clone(),
+        }
+    }
+
+    fn query(&self) -> QueryParams {
+        self.query.clone()
+    }
+
+    fn method(&self) -> Method {
+        self.method.clone()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Page<R> {
+    #[serde(default = "Vec::new")]
+    items: Vec<R>,
+    next_page_token: Option<String>,
+}
+
+/// ResourceClient provides the ResourceApi operations for any Resource.
+///
+/// ```ignore
+/// let events = ResourceClient::<Event>::new(client.clone(), "primary");
+/// let event = events.get("abc123").await?;
+///
+/// let calendars = ResourceClient::<CalendarListItem>::top_level(client);
+/// let list = calendars.list(Default::default()).await?;
+/// ```
+#[derive(Debug)]
+pub struct ResourceClient<R> {
+    client: Client,
+    parent: String,
+    fields: Option<Fields>,
+    resource: PhantomData<fn() -> R>,
+}
+
+impl<R> Clone for ResourceClient<R> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            parent: self.parent.clone(),
+            fields: self.fields.clone(),
+            resource: PhantomData,
+        }
+    }
+}
+
+impl<R: Resource> ResourceClient<R> {
+    /// A client for the collection belonging to the parent, e.g. the events of a calendar.
+    pub fn new(client: Client, parent: impl Into<String>) -> Self {
+        Self {
+            client,
+            parent: parent.into(),
+            fields: None,
+            resource: PhantomData,
+        }
+    }
+
+    /// A client for a collection which has no parent, such as the calendar list.
+    pub fn top_level(client: Client) -> Self {
+        Self::new(client, "")
+    }
+
+    /// Only request the selected fields when getting and listing. Fields which are not selected
+    /// are left unset.
+    pub fn with_fields(mut self, fields: Fields) -> Self {
+        self.fields = Some(fields);
+        self
+    }
+
+    fn target<B, T>(&self, method: Method, id: Option<&str>, body: B) -> Target<B, T> {
+        let mut path = R::collection_path(&path_segment(&self.parent));
+        if let Some(id) = id {
+            path.push('/');
+            path.push_str(&path_segment(id));
+        }
+
+        Target {
+            method,
+            path,
+            query: QueryParams::new(),
+            body,
+            response: PhantomData,
+        }
+    }
+
+    async fn send_resource<B: Serialize>(
+        &self,
+        method: Method,
+        id: Option<&str>,
+        body: B,
+    ) -> Result<ApiResponse<R>, ClientError> {
+        let mut target = self.target(method.clone(), id, body);
+        if method == Method::GET {
+            if let Some(fields) = &self.fields {
+                target.query.insert("fields".to_string(), fields.mask());
+            }
+        }
+
+        Ok(self
+            .client
+            .send_with_meta(target)
+            .await?
+            .map(|mut resource: R| {
+                resource.set_parent(&self.parent);
+                resource
+            }))
+    }
+
+    pub async fn get_with_meta(&self, id: &str) -> Result<ApiResponse<R>, ClientError> {
+        self.send_resource(Method::GET, Some(id), ()).await
+    }
+
+    pub async fn insert_with_meta(&self, resource: R) -> Result<ApiResponse<R>, ClientError> {
+        self.send_resource(Method::POST, None, resource).await
+    }
+
+    pub async fn update_with_meta(&self, resource: R) -> Result<ApiResponse<R>, ClientError> {
+        let id = resource
+            .resource_id()
+            .ok_or_else(|| ClientError::UnknownError("resource has no id".to_string()))?;
+        self.send_resource(Method::PUT, Some(&id), resource).await
+    }
+
+    pub async fn patch_with_meta(
+        &self,
+        id: &str,
+        patch: serde_json::Value,
+    ) -> Result<ApiResponse<R>, ClientError> {
+        self.send_resource(Method::PATCH, Some(id), patch).await
+    }
+
+    pub async fn delete_with_meta(&self, id: &str) -> Result<ApiResponse<()>, ClientError> {
+        self.client
+            .send_with_meta(self.target::<(), ()>(Method::DELETE, Some(id), ()))
+            .await
+    }
+}
+
+impl<R: ListableResource> ResourceClient<R> {
+    /// List the collection as with ResourceListApi::list, returning the items of each page along
+    /// with the metadata of the response they came from.
+    pub async fn list_with_meta(
+        &self,
+        query: QueryParams,
+    ) -> Result<Vec<ApiResponse<Vec<R>>>, ClientError> {
+        let pages = collect_pages(
+            |page_token| {
+                let target = self.list_target(&query, page_token);
+                self.client.send_with_meta(target)
+            },
+            |page: &Page<R>| page.next_page_token.clone(),
+        )
+        .await?;
+
+        Ok(pages
+            .into_iter()
+            .map(|page| {
+                page.map(|page| {
+                    page.items
+                        .into_iter()
+                        .map(|mut item| {
+                            item.set_parent(&self.parent);
+                            item
+                        })
+                        .collect()
+                })
+            })
+            .collect())
+    }
+
+    pub async fn watch_with_meta(
+        &self,
+        channel: Channel,
+    ) -> Result<ApiResponse<Channel>, ClientError> {
+        self.client
+            .send_with_meta(self.target::<Channel, Channel>(Method::POST, Some("watch"), channel))
+            .await
+    }
+
+    fn list_target(&self, query: &QueryParams, page_token: Option<String>) -> Target<(), Page<R>> {
+        let mut target = self.target(Method::GET, None, ());
+        target.query = query.clone();
+        if let Some(fields) = &self.fields {
+            target
+                .query
+                .insert("fields".to_string(), fields.list_mask());
+        }
+        if let Some(page_token) = page_token {
+            target.query.insert("pageToken".to_string(), page_token);
+        }
+        target
+    }
+
+    /// Stream the collection, following nextPageToken until all pages have been retrieved.
+    pub fn list_stream(&self, query: QueryParams) -> impl Stream<Item = Result<R, ClientError>> {
+        let this = self.clone();
+
+        paginate(move |page_token| {
+            let client = this.client.clone();
+            let parent = this.parent.clone();
+            let target = this.list_target(&query, page_token);
+
+            async move {
+                let page = client.send(target).await?;
+                let items = page
+                    .items
+                    .into_iter()
+                    .map(|mut item: R| {
+                        item.set_parent(&parent);
+                        item
+                    })
+                    .collect();
+                Ok((items, page.next_page_token))
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl<R: Resource> ResourceApi<R> for ResourceClient<R> {
+    async fn get(&self, id: &str) -> Result<R, ClientError> {
+        self.get_with_meta(id).await.map(ApiResponse::into_inner)
+    }
+
+    async fn insert(&self, resource: R) -> Result<R, ClientError> {
+        self.insert_with_meta(resource)
+            .await
+            .map(ApiResponse::into_inner)
+    }
+
+    async fn update(&self, resource: R) -> Result<R, ClientError> {
+        self.update_with_meta(resource)
+            .await
+            .map(ApiResponse::into_inner)
+    }
+
+    async fn patch(&self, id: &str, patch: serde_json::Value) -> Result<R, ClientError> {
+        self.patch_with_meta(id, patch)
+            .await
+            .map(ApiResponse::into_inner)
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), ClientError> {
+        self.delete_with_meta(id).await.map(ApiResponse::into_inner)
+    }
+}
+
+#[async_trait]
+impl<R: ListableResource> ResourceListApi<R> for ResourceClient<R> {
+    async fn list(&self, query: QueryParams) -> Result<Vec<R>, ClientError> {
+        collect_all(self.list_stream(query)).await
+    }
+
+    async fn watch(&self, channel: Channel) -> Result<Channel, ClientError> {
+        self.watch_with_meta(channel)
+            .await
+            .map(ApiResponse::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_encodes_path_segments() {
+        let client = Client::new("token".to_string()).unwrap();
+        let events =
+            ResourceClient::<Event>::new(client, "en.usa#holiday@group.v.calendar.google.com");
+        let target: Target<(), Event> = events.target(Method::GET, Some("a/b?c"), ());
+
+        assert_eq!(
+            target
+                .url("https://www.googleapis.com/calendar/v3", None)
+                .unwrap()
+                .path()
+                .to_string(),
+            "/calendar/v3/calendars/en.usa%23holiday@group.v.calendar.google.com/events/a%2Fb%3Fc"
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{
+    client::ClientError,
+    transport::{TransportError, TransportErrorKind},
+};
+use rand::Rng;
+use reqwest::{header::HeaderMap, Method};
+use std::time::Duration;
+
+/// RetryPolicy controls how the Client retries requests which fail with rate-limit or transient
+/// errors (403 rateLimitExceeded, 429 and 5xx), as well as connection failures. Timeouts are only
+/// retried for idempotent methods, since the request may have been carried out. Delays grow
+/// exponentially from initial_backoff up to max_backoff. A Retry-After header sent by the server
+/// takes precedence over the computed delay; if it asks for longer than max_backoff, the error is
+/// returned instead of waiting.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one. 1 disables retries.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Randomize each delay between zero and the computed backoff ("full jitter"), so that many
+    /// clients hitting the same limit do not retry in lockstep.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(32),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy which never retries. This is what Client::new uses.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Returns true if another attempt may follow the given (1-based) attempt.
+    pub fn should_retry(&self, attempt: u32) -> bool {
+        attempt < self.max_attempts
+    }
+
+    /// Returns true if the server's Retry-After, if any, is short enough to wait for.
+    pub fn accepts_retry_after(&self, retry_after: Option<Duration>) -> bool {
+        retry_after.is_none_or(|retry_after| retry_after <= self.max_backoff)
+    }
+
+    /// The delay to wait after the given (1-based) attempt has failed. It is never longer than
+    /// max_backoff.
+    pub fn backoff(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_backoff);
+        }
+
+        let exp = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff);
+
+        if self.jitter && !exp.is_zero() {
+            rand::thread_rng().gen_range(Duration::ZERO..=exp)
+        } else {
+            exp
+        }
+    }
+}
+
+/// Errors worth retrying: rate limiting (429, or 403 with a rate limit reason) and server errors.
+/// Other 403s are permission problems and are returned immediately.
+pub(crate) fn retryable_error(err: &ClientError) -> bool {
+    matches!(err, ClientError::RateLimited(_))
+        || err.status().is_some_and(|status| status.is_server_error())
+}
+
+/// Transport failures worth retrying. A request which failed to connect was never sent, but one
+/// which timed out may have been carried out, so it is only retried if repeating it is harmless.
+pub(crate) fn retryable_transport_error(err: &TransportError, method: &Method) -> bool {
+    match err.kind {
+        TransportErrorKind::Connect => true,
+        TransportErrorKind::Timeout => method.is_idempotent(),
+        TransportErrorKind::Other => false,
+    }
+}
+
+/// Parse the Retry-After header, which may be either a number of seconds or an HTTP date.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+
+    if let Ok(secs) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+        .to_std()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderValue, RETRY_AFTER};
+
+    fn headers(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn policy(jitter: bool) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            jitter,
+        }
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_to_the_cap() {
+        let policy = policy(false);
+        assert_eq!(policy.backoff(1, None), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2, None), Duration::from_millis(200));
+        assert_eq!(policy.backoff(4, None), Duration::from_millis(800));
+        assert_eq!(policy.backoff(5, None), Duration::from_secs(1));
+        assert_eq!(policy.backoff(u32::MAX, None), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn jitter_stays_within_the_backoff() {
+        let jittered = policy(true);
+        for attempt in 1..=6 {
+            let cap = policy(false).backoff(attempt, None);
+            for _ in 0..50 {
+                assert!(jittered.backoff(attempt, None) <= cap);
+            }
+        }
+    }
+
+    #[test]
+    fn retry_after_is_capped() {
+        let policy = policy(true);
+        let short = Some(Duration::from_millis(300));
+        let long = Some(Duration::from_secs(3600));
+
+        assert_eq!(policy.backoff(1, short), Duration::from_millis(300));
+        assert_eq!(policy.backoff(1, long), Duration::from_secs(1));
+        assert!(policy.accepts_retry_after(None));
+        assert!(policy.accepts_retry_after(short));
+        assert!(!policy.accepts_retry_after(long));
+    }
+
+    #[test]
+    fn timeouts_are_retried_only_when_idempotent() {
+        let timeout = TransportError::new(TransportErrorKind::Timeout, "timed out");
+        let connect = TransportError::new(TransportErrorKind::Connect, "refused");
+
+        assert!(retryable_transport_error(&timeout, &Method::GET));
+        assert!(retryable_transport_error(&timeout, &Method::PUT));
+        assert!(!retryable_transport_error(&timeout, &Method::POST));
+        assert!(!retryable_transport_error(&timeout, &Method::PATCH));
+        assert!(retryable_transport_error(&connect, &Method::POST));
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        assert_eq!(retry_after(&headers("120")), Some(Duration::from_secs(120)));
+        assert_eq!(retry_after(&headers(" 0 ")), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retry_after_http_date() {
+        let date = (chrono::Utc::now() + chrono::Duration::seconds(90)).to_rfc2822();
+        let wait = retry_after(&headers(&date)).unwrap();
+        assert!(wait > Duration::from_secs(80) && wait <= Duration::from_secs(90));
+    }
+
+    #[test]
+    fn retry_after_past_or_invalid() {
+        assert_eq!(retry_after(&headers("Wed, 21 Oct 2015 07:28:00 GMT")), None);
+        assert_eq!(retry_after(&headers("soon")), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+}
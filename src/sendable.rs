@@ -1,25 +1,98 @@
 use crate::client::ClientError;
+use reqwest::Method;
 use std::collections::BTreeMap;
 use url::Url;
 
-const BASE_URL: &str = "https://www.googleapis.com/calendar/v3";
+/// The default root of the Google Calendar v3 API. Clients may be pointed elsewhere with
+/// Client::set_base_url.
+pub const BASE_URL: &str = "https://www.googleapis.com/calendar/v3";
 
 pub type QueryParams = BTreeMap<String, String>;
 pub type AdditionalProperties = BTreeMap<String, String>;
 
+/// Fields selects which fields of a resource Google should return, using the `fields` partial
+/// response parameter. Field names are the camelCase names used by the API.
+///
+/// ```
+/// use gcal::sendable::Fields;
+///
+/// let fields = Fields::new()
+///     .field("id")
+///     .field("summary")
+///     .nested("start", Fields::new().field("dateTime"));
+/// assert_eq!(fields.mask(), "id,start(dateTime),summary");
+/// assert_eq!(fields.list_mask(), "nextPageToken,items(id,start(dateTime),summary)");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fields(BTreeMap<String, Option<Fields>>);
+
+impl Fields {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Select a field in its entirety.
+    pub fn field(mut self, name: impl Into<String>) -> Self {
+        self.0.insert(name.into(), None);
+        self
+    }
+
+    /// Select only some sub-fields of a field.
+    pub fn nested(mut self, name: impl Into<String>, fields: Fields) -> Self {
+        self.0.insert(name.into(), Some(fields));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The mask for a request returning a single resource.
+    pub fn mask(&self) -> String {
+        self.0
+            .iter()
+            .map(|(name, sub)| match sub {
+                Some(sub) if !sub.is_empty() => format!("{}({})", name, sub.mask()),
+                _ => name.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// The mask for a list request returning these resources as items. nextPageToken is always
+    /// included so pagination keeps working.
+    pub fn list_mask(&self) -> String {
+        format!("nextPageToken,items({})", self.mask())
+    }
+}
+
 /// Sendable is the trait you must implement to interact with the Client. This object is received
 /// by the client and is used to construct the request URL as well as manage the (de)serialization
-/// of the object.
+/// of the object. Client::send uses method and action to issue the request, and deserializes the
+/// result as Response.
 pub trait Sendable
 where
     Self: serde::Serialize,
 {
+    /// The type the API responds with. Use `()` for endpoints which respond with no content.
+    type Response: serde::de::DeserializeOwned;
+
     fn path(&self, action: Option<String>) -> String;
     fn query(&self) -> BTreeMap<String, String>;
 
-    fn url(&self, action: Option<String>) -> Result<Url, ClientError> {
+    /// The method Client::send uses. Defaults to GET.
+    fn method(&self) -> Method {
+        Method::GET
+    }
+
+    /// The action Client::send appends to the path. Defaults to none.
+    fn action(&self) -> Option<String> {
+        None
+    }
+
+    fn url(&self, base_url: &str, action: Option<String>) -> Result<Url, ClientError> {
         Ok(Url::parse_with_params(
-            &format!("{}/{}", BASE_URL, self.path(action)),
+            &format!("{}/{}", base_url.trim_end_matches('/'), self.path(action)),
             self.query(),
         )?)
     }
@@ -28,3 +101,64 @@ where
         Ok(serde_json::to_vec(self)?)
     }
 }
+
+/// Percent-encode a value for use as a single path segment, so that IDs containing characters
+/// such as `/`, `#` or `?` stay within their segment.
+pub(crate) fn path_segment(segment: &str) -> String {
+    let mut url = Url::parse("http://localhost/").expect("static URL is valid");
+    url.path_segments_mut()
+        .expect("http URLs have a path")
+        .pop()
+        .push(segment);
+    url.path()[1..].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_segments_are_encoded() {
+        assert_eq!(path_segment("primary"), "primary");
+        assert_eq!(
+            path_segment("en.usa#holiday@group.v.calendar.google.com"),
+            "en.usa%23holiday@group.v.calendar.google.com"
+        );
+        assert_eq!(path_segment("a/b?c%d e"), "a%2Fb%3Fc%25d%20e");
+    }
+
+    #[test]
+    fn empty_mask() {
+        assert_eq!(Fields::new().mask(), "");
+        assert!(Fields::new().is_empty());
+    }
+
+    #[test]
+    fn mask_is_sorted() {
+        let fields = Fields::new().field("summary").field("id").field("etag");
+        assert_eq!(fields.mask(), "etag,id,summary");
+    }
+
+    #[test]
+    fn nested_mask() {
+        let fields = Fields::new().field("id").nested(
+            "organizer",
+            Fields::new()
+                .field("email")
+                .nested("self", Fields::new().field("x")),
+        );
+        assert_eq!(fields.mask(), "id,organizer(email,self(x))");
+    }
+
+    #[test]
+    fn empty_nested_selects_whole_field() {
+        let fields = Fields::new().nested("start", Fields::new());
+        assert_eq!(fields.mask(), "start");
+    }
+
+    #[test]
+    fn list_mask_keeps_page_token() {
+        let fields = Fields::new().field("id");
+        assert_eq!(fields.list_mask(), "nextPageToken,items(id)");
+    }
+}
@@ -0,0 +1,154 @@
+use crate::client::ClientError;
+use crate::transport::HttpResponse;
+use reqwest::Method;
+use std::{collections::hash_map::RandomState, hash::BuildHasher, sync::OnceLock, time::Duration};
+use tracing::{field::Empty, Span};
+use url::Url;
+
+/// Path segments which are part of the API's structure rather than identifiers, and are kept
+/// verbatim in span paths.
+const KNOWN_SEGMENTS: &[&str] = &[
+    "batch",
+    "calendar",
+    "v3",
+    "calendars",
+    "events",
+    "users",
+    "me",
+    "calendarList",
+    "instances",
+    "import",
+    "move",
+    "quickAdd",
+    "watch",
+    "clear",
+    "acl",
+    "settings",
+    "colors",
+    "freeBusy",
+    "channels",
+    "stop",
+];
+
+/// The key identifiers are hashed with, chosen at random for each process, so that a token
+/// cannot be matched against the hashes of guessed email addresses or calendar IDs.
+static REDACTION_KEY: OnceLock<RandomState> = OnceLock::new();
+
+/// Render the URL path with calendar, event and other identifiers replaced by keyed hashes.
+pub(crate) fn redact_path(url: &Url) -> String {
+    url.path()
+        .split('/')
+        .map(|segment| {
+            if segment.is_empty() || KNOWN_SEGMENTS.contains(&segment) {
+                segment.to_string()
+            } else {
+                // a token which is stable within the process, so spans for the same calendar or
+                // event can still be correlated.
+                let hash = REDACTION_KEY
+                    .get_or_init(RandomState::new)
+                    .hash_one(segment);
+                format!("{{{:08x}}}", hash as u32)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub(crate) fn request_span(method: &Method, url: &Url) -> Span {
+    tracing::info_span!(
+        "gcal.request",
+        http.method = %method,
+        http.path = %redact_path(url),
+        http.status = Empty,
+        retries = Empty,
+        latency_ms = Empty,
+        error = Empty,
+    )
+}
+
+pub(crate) fn record_retries(attempt: u32) {
+    Span::current().record("retries", attempt.saturating_sub(1));
+}
+
+pub(crate) fn record_result(
+    span: &Span,
+    res: &Result<HttpResponse, ClientError>,
+    elapsed: Duration,
+) {
+    span.record("latency_ms", elapsed.as_millis() as u64);
+
+    match res {
+        Ok(resp) => {
+            span.record("http.status", resp.status().as_u16());
+        }
+        Err(err) => {
+            if let Some(status) = err.status() {
+                span.record("http.status", status.as_u16());
+            }
+            span.record("error", error_kind(err));
+        }
+    }
+}
+
+/// The name of the error's variant. The message is not recorded, since API errors and decode
+/// errors carry parts of response bodies, which hold calendar data.
+fn error_kind(err: &ClientError) -> &'static str {
+    match err {
+        ClientError::InvalidToken => "InvalidToken",
+        ClientError::BadRequest(_) => "BadRequest",
+        ClientError::Forbidden(_) => "Forbidden",
+        ClientError::NotFound(_) => "NotFound",
+        ClientError::Conflict(_) => "Conflict",
+        ClientError::Gone(_) => "Gone",
+        ClientError::PreconditionFailed(_) => "PreconditionFailed",
+        ClientError::RateLimited(_) => "RateLimited",
+        ClientError::QuotaExceeded(_) => "QuotaExceeded",
+        ClientError::ApiError(_) => "ApiError",
+        ClientError::Transport(_) => "Transport",
+        ClientError::Decode(_) => "Decode",
+        ClientError::InvalidEventId(_) => "InvalidEventId",
+        ClientError::UnknownError(_) => "UnknownError",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(path: &str) -> String {
+        redact_path(&Url::parse(&format!("https://www.googleapis.com{}", path)).unwrap())
+    }
+
+    #[test]
+    fn keeps_known_segments() {
+        assert_eq!(
+            path("/calendar/v3/users/me/calendarList"),
+            "/calendar/v3/users/me/calendarList"
+        );
+    }
+
+    #[test]
+    fn hides_identifiers() {
+        let redacted = path("/calendar/v3/calendars/someone@example.com/events/abc123");
+
+        assert!(!redacted.contains("someone"));
+        assert!(!redacted.contains("abc123"));
+        assert!(redacted.starts_with("/calendar/v3/calendars/{"));
+        assert_eq!(redacted.matches('{').count(), 2);
+    }
+
+    #[test]
+    fn tokens_are_stable_and_keyed() {
+        let first = path("/calendar/v3/calendars/someone@example.com/events");
+        let second = path("/calendar/v3/calendars/someone@example.com/events");
+        assert_eq!(first, second);
+        assert_ne!(
+            first,
+            path("/calendar/v3/calendars/other@example.com/events")
+        );
+
+        // the unkeyed FNV-1a hash of the identifier, which anyone could compute.
+        let unkeyed = crate::cache::fnv1a("someone@example.com") as u32;
+        assert!(!first.contains(&format!("{:08x}", unkeyed)));
+    }
+}
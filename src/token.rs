@@ -0,0 +1,107 @@
+use crate::{
+    client::ClientError,
+    oauth::{request_access_token, State},
+};
+use async_trait::async_trait;
+
+/// How long before its recorded expiry a token is considered stale and refreshed pre-emptively.
+pub(crate) const EXPIRY_MARGIN_SECS: i64 = 60;
+
+/// TokenProvider supplies the Client with access tokens. The client asks for a token before each
+/// request, and calls refresh once if the API rejects it as invalid before retrying the request.
+#[async_trait]
+pub trait TokenProvider: std::fmt::Debug + Send + Sync {
+    /// Return a token which is currently believed to be valid.
+    async fn token(&self) -> Result<String, ClientError>;
+
+    /// Obtain a new token after the API rejected `rejected`. Implementations may return a token
+    /// obtained concurrently by another request if it differs from the rejected one.
+    async fn refresh(&self, rejected: &str) -> Result<String, ClientError>;
+}
+
+/// StaticToken is a fixed access key which cannot be refreshed. This is what Client::new uses.
+#[derive(Clone)]
+pub struct StaticToken(String);
+
+impl StaticToken {
+    pub fn new(access_key: String) -> Self {
+        Self(access_key)
+    }
+}
+
+impl std::fmt::Debug for StaticToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StaticToken(<redacted>)")
+    }
+}
+
+#[async_trait]
+impl TokenProvider for StaticToken {
+    async fn token(&self) -> Result<String, ClientError> {
+        Ok(self.0.clone())
+    }
+
+    async fn refresh(&self, _rejected: &str) -> Result<String, ClientError> {
+        Err(ClientError::InvalidToken)
+    }
+}
+
+/// RefreshingToken keeps the ClientParameters in the shared State up to date, using the stored
+/// refresh_token to obtain a new access key whenever the current one expires or is rejected. The
+/// same State used with oauth_listener may be passed here once the access key has been captured.
+#[derive(Debug, Clone)]
+pub struct RefreshingToken(State);
+
+impl RefreshingToken {
+    pub fn new(state: State) -> Self {
+        Self(state)
+    }
+
+    /// The parameters backing this provider, including the current access key.
+    pub fn state(&self) -> State {
+        self.0.clone()
+    }
+}
+
+#[async_trait]
+impl TokenProvider for RefreshingToken {
+    async fn token(&self) -> Result<String, ClientError> {
+        let mut lock = self.0.lock().await;
+
+        let fresh = lock.expires_at.is_none_or(|expires_at| {
+            expires_at - chrono::Duration::seconds(EXPIRY_MARGIN_SECS)
+                > chrono::Local::now().naive_utc()
+        });
+
+        match lock.access_key.clone() {
+            Some(access_key) if fresh => Ok(access_key),
+            _ => {
+                if lock.refresh_token.is_none() {
+                    return Err(ClientError::InvalidToken);
+                }
+
+                let token = request_access_token(lock.clone(), None, None, true).await?;
+                lock.apply_token(token);
+                Ok(lock.access_key.clone().unwrap_or_default())
+            }
+        }
+    }
+
+    async fn refresh(&self, rejected: &str) -> Result<String, ClientError> {
+        let mut lock = self.0.lock().await;
+
+        if let Some(access_key) = &lock.access_key {
+            if access_key != rejected {
+                return Ok(access_key.clone());
+            }
+        }
+
+        if lock.refresh_token.is_none() {
+            return Err(ClientError::InvalidToken);
+        }
+
+        let token = request_access_token(lock.clone(), None, None, true).await?;
+        lock.apply_token(token);
+        Ok(lock.access_key.clone().unwrap_or_default())
+    }
+}
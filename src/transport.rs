@@ -0,0 +1,241 @@
+use crate::client::{ClientError, DecodeError};
+use async_trait::async_trait;
+use reqwest::{header::HeaderMap, ClientBuilder, Method, StatusCode};
+use url::Url;
+
+/// HttpRequest is a fully prepared request, including the Authorization header, handed to the
+/// Transport.
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    pub body: Option<Vec<u8>>,
+}
+
+/// HttpResponse is a response with its body already read, so it may be inspected by middleware
+/// and recorded without consuming it.
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.body
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).to_string()
+    }
+
+    /// Deserialize the response body. An empty body is treated as `null`, so it may be
+    /// deserialized as `()` or an Option.
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T, ClientError> {
+        decode(&self.body)
+    }
+}
+
+/// How many bytes either side of a decoding failure are included in DecodeError::snippet.
+const SNIPPET_CONTEXT: usize = 40;
+
+/// Deserialize the body, reporting where in it deserialization failed.
+pub(crate) fn decode<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, ClientError> {
+    let body = if body.iter().all(u8::is_ascii_whitespace) {
+        b"null".as_slice()
+    } else {
+        body
+    };
+
+    let de = &mut serde_json::Deserializer::from_slice(body);
+    serde_path_to_error::deserialize(de).map_err(|e| {
+        let path = e.path().to_string();
+        let inner = e.into_inner();
+
+        // line and column are 1-based; find the byte offset of the failure.
+        let offset = body
+            .split(|b| *b == b'\n')
+            .take(inner.line().saturating_sub(1))
+            .map(|line| line.len() + 1)
+            .sum::<usize>()
+            + inner.column().saturating_sub(1);
+        let offset = offset.min(body.len());
+        let snippet = &body
+            [offset.saturating_sub(SNIPPET_CONTEXT)..(offset + SNIPPET_CONTEXT).min(body.len())];
+
+        ClientError::Decode(DecodeError {
+            path,
+            message: inner.to_string(),
+            snippet: String::from_utf8_lossy(snippet).to_string(),
+        })
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportErrorKind {
+    /// The connection could not be established.
+    Connect,
+    /// The request or the connection timed out.
+    Timeout,
+    Other,
+}
+
+/// TransportError is a failure to send a request or receive its response. Connect errors, and
+/// Timeout errors of idempotent requests, are retried according to the RetryPolicy.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{message}")]
+pub struct TransportError {
+    pub kind: TransportErrorKind,
+    pub message: String,
+}
+
+impl TransportError {
+    pub fn new(kind: TransportErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self.kind,
+            TransportErrorKind::Connect | TransportErrorKind::Timeout
+        )
+    }
+}
+
+impl From<reqwest::Error> for TransportError {
+    fn from(value: reqwest::Error) -> Self {
+        let kind = if value.is_timeout() {
+            TransportErrorKind::Timeout
+        } else if value.is_connect() {
+            TransportErrorKind::Connect
+        } else {
+            TransportErrorKind::Other
+        };
+
+        Self::new(kind, value.to_string())
+    }
+}
+
+/// Transport sends requests on behalf of the Client. ReqwestTransport is used by default;
+/// implement this to substitute a fake for unit tests, a different TLS stack or an egress proxy
+/// library, and install it with Client::set_transport.
+#[async_trait]
+pub trait Transport: std::fmt::Debug + Send + Sync {
+    async fn execute(&self, req: HttpRequest) -> Result<HttpResponse, TransportError>;
+}
+
+/// ReqwestTransport is the default Transport, backed by a reqwest client.
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport(reqwest::Client);
+
+impl ReqwestTransport {
+    /// Build a transport with gzip enabled. https_only should only be false for local test
+    /// servers.
+    pub fn new(https_only: bool) -> Result<Self, ClientError> {
+        Ok(Self(
+            ClientBuilder::new()
+                .gzip(true)
+                .https_only(https_only)
+                .build()?,
+        ))
+    }
+
+    /// Use an already configured reqwest client.
+    pub fn from_client(client: reqwest::Client) -> Self {
+        Self(client)
+    }
+}
+
+#[async_trait]
+impl Transport for ReqwestTransport {
+    async fn execute(&self, req: HttpRequest) -> Result<HttpResponse, TransportError> {
+        let mut builder = self.0.request(req.method, req.url).headers(req.headers);
+        if let Some(body) = req.body {
+            builder = builder.body(body);
+        }
+
+        let resp = builder.send().await?;
+
+        Ok(HttpResponse {
+            status: resp.status(),
+            headers: resp.headers().clone(),
+            body: resp.bytes().await?.to_vec(),
+        })
+    }
+}
+
+/// A Transport for unit tests which records requests and replays queued responses.
+#[cfg(test)]
+pub(crate) mod fake {
+    use super::*;
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+    };
+
+    #[derive(Debug, Default)]
+    struct Inner {
+        responses: VecDeque<Result<HttpResponse, TransportError>>,
+        requests: Vec<HttpRequest>,
+    }
+
+    /// FakeTransport answers each request with the next queued response, or an empty 200 once
+    /// they run out. Clones share the queue and the record of requests.
+    #[derive(Debug, Clone, Default)]
+    pub(crate) struct FakeTransport(Arc<Mutex<Inner>>);
+
+    impl FakeTransport {
+        pub(crate) fn new() -> Self {
+            Self::default()
+        }
+
+        pub(crate) fn respond(&self, status: StatusCode, headers: HeaderMap, body: &str) {
+            self.0.lock().unwrap().responses.push_back(Ok(HttpResponse {
+                status,
+                headers,
+                body: body.as_bytes().to_vec(),
+            }));
+        }
+
+        pub(crate) fn respond_json(&self, status: StatusCode, body: &str) {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                reqwest::header::CONTENT_TYPE,
+                reqwest::header::HeaderValue::from_static("application/json"),
+            );
+            self.respond(status, headers, body)
+        }
+
+        pub(crate) fn requests(&self) -> Vec<HttpRequest> {
+            self.0.lock().unwrap().requests.clone()
+        }
+    }
+
+    #[async_trait]
+    impl Transport for FakeTransport {
+        async fn execute(&self, req: HttpRequest) -> Result<HttpResponse, TransportError> {
+            let mut inner = self.0.lock().unwrap();
+            inner.requests.push(req);
+            inner.responses.pop_front().unwrap_or_else(|| {
+                Ok(HttpResponse {
+                    status: StatusCode::OK,
+                    headers: HeaderMap::new(),
+                    body: b"{}".to_vec(),
+                })
+            })
+        }
+    }
+}